@@ -10,7 +10,13 @@ use crate::watchalong::commands::{
 use crate::watchalong::timer::Timer;
 use crate::ytdl::commands::AppState;
 use crate::ytdl::commands::{
-    download_video_command, fetch_video, get_default_download_path, resize_window,
+    cancel_download, check_output_template, check_url, clear_history, delete_cookies,
+    download_video_command, export_archive, export_logs, fetch_formats, fetch_video,
+    get_default_download_path, get_settings, has_imported_cookies, import_archive, import_cookies,
+    list_archive, list_downloads, list_history, list_logs, move_download, pause_download,
+    preview_output_path, read_log, redownload, remove_download, remove_from_archive, resize_window,
+    resume_download, sanitize_filename, set_max_concurrent_downloads, stop_recording,
+    update_settings,
 };
 use crate::ytdl::deps::{download_deps, verify_deps};
 
@@ -48,7 +54,35 @@ fn main() {
             add_episode,
             dec_episode,
             download_video_command,
+            list_downloads,
+            move_download,
+            remove_download,
+            cancel_download,
+            pause_download,
+            resume_download,
+            stop_recording,
+            set_max_concurrent_downloads,
             fetch_video,
+            fetch_formats,
+            check_url,
+            preview_output_path,
+            check_output_template,
+            sanitize_filename,
+            import_cookies,
+            has_imported_cookies,
+            delete_cookies,
+            list_archive,
+            remove_from_archive,
+            import_archive,
+            export_archive,
+            list_history,
+            clear_history,
+            redownload,
+            list_logs,
+            read_log,
+            export_logs,
+            get_settings,
+            update_settings,
             resize_window,
             get_default_download_path,
             download_deps,
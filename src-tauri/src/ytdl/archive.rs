@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ytdl::settings::config_dir;
+
+/// A downloaded video, as yt-dlp writes it to the archive (`youtube dQw4w9WgXcQ`).
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ArchiveEntry {
+    /// yt-dlp's extractor key in lowercase
+    pub(crate) extractor: String,
+    pub(crate) id: String,
+}
+
+impl ArchiveEntry {
+    fn parse(line: &str) -> Option<ArchiveEntry> {
+        let (extractor, id) = line.trim().split_once(' ')?;
+        if extractor.is_empty() || id.is_empty() {
+            return None;
+        }
+
+        Some(ArchiveEntry {
+            extractor: extractor.to_string(),
+            id: id.to_string(),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!("{} {}", self.extractor, self.id)
+    }
+}
+
+/// The archive of downloaded videos, kept in the app config directory.
+pub(crate) fn archive_path() -> PathBuf {
+    config_dir().join("archive.txt")
+}
+
+/// Builds the yt-dlp arguments that skip archived videos and archive the downloaded ones.
+pub(crate) fn archive_args() -> Vec<String> {
+    vec![
+        "--download-archive".into(),
+        archive_path().to_str().unwrap().into(),
+    ]
+}
+
+/// Reads the archive, skipping invalid lines. A missing archive is empty, but one that can't be
+/// read is an error, so it's not overwritten with the entries that could be read.
+pub(crate) fn read_archive() -> Result<Vec<ArchiveEntry>, Box<dyn Error>> {
+    match fs::read_to_string(archive_path()) {
+        Ok(archive) => Ok(parse_archive(&archive)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read the download archive: {}", e).into()),
+    }
+}
+
+/// The archive entries as a set of `extractor id` lines, to check videos against it.
+pub(crate) fn archived_ids() -> Result<HashSet<String>, Box<dyn Error>> {
+    Ok(read_archive()?.iter().map(ArchiveEntry::to_line).collect())
+}
+
+/// Removes the given entries from the archive, so the videos can be downloaded again.
+pub(crate) fn remove_archive_entries(entries: &[ArchiveEntry]) -> Result<(), Box<dyn Error>> {
+    let removed: HashSet<&ArchiveEntry> = entries.iter().collect();
+    let archive: Vec<ArchiveEntry> = read_archive()?
+        .into_iter()
+        .filter(|entry| !removed.contains(entry))
+        .collect();
+
+    write_archive(&archive)
+}
+
+/// Adds the entries of another archive file that are not in the archive yet. Returns how many
+/// entries were added.
+pub(crate) fn import_archive(path: &str) -> Result<usize, Box<dyn Error>> {
+    let mut archive = read_archive()?;
+    let mut known: HashSet<ArchiveEntry> = archive.iter().cloned().collect();
+    let before = archive.len();
+
+    for entry in read_archive_file(Path::new(path))? {
+        if known.insert(entry.clone()) {
+            archive.push(entry);
+        }
+    }
+
+    write_archive(&archive)?;
+
+    Ok(archive.len() - before)
+}
+
+/// Writes a copy of the archive to `path`, in yt-dlp's format.
+pub(crate) fn export_archive(path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, archive_lines(&read_archive()?))?;
+
+    Ok(())
+}
+
+fn read_archive_file(path: &Path) -> Result<Vec<ArchiveEntry>, Box<dyn Error>> {
+    Ok(parse_archive(&fs::read_to_string(path)?))
+}
+
+fn parse_archive(archive: &str) -> Vec<ArchiveEntry> {
+    archive.lines().filter_map(ArchiveEntry::parse).collect()
+}
+
+fn write_archive(archive: &[ArchiveEntry]) -> Result<(), Box<dyn Error>> {
+    let path = archive_path();
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, archive_lines(archive))?;
+
+    Ok(())
+}
+
+fn archive_lines(archive: &[ArchiveEntry]) -> String {
+    archive
+        .iter()
+        .map(|entry| format!("{}\n", entry.to_line()))
+        .collect()
+}
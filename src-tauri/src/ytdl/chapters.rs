@@ -0,0 +1,80 @@
+use regex::escape;
+use serde::{Deserialize, Serialize};
+
+/// Output file name of each chapter when a video is split by chapters.
+pub(crate) const CHAPTER_FILE_TEMPLATE: &str =
+    "%(title)s - %(section_number)03d %(section_title)s.%(ext)s";
+
+/// Output file name when only some chapters are downloaded, each as its own file.
+pub(crate) const SELECTED_CHAPTER_FILE_TEMPLATE: &str = "%(title)s - %(section_title)s.%(ext)s";
+
+/// A chapter of a video, as in yt-dlp's `chapters` field.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct Chapter {
+    pub(crate) title: String,
+    /// Seconds
+    pub(crate) start_time: f64,
+    /// Seconds
+    pub(crate) end_time: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ChapterOptions {
+    /// Write the chapters as chapter markers of the container
+    #[serde(default = "default_embed")]
+    pub(crate) embed: bool,
+    /// Save one file per chapter
+    #[serde(default)]
+    pub(crate) split: bool,
+    /// Titles of the chapters to keep when splitting, all of them when empty
+    #[serde(default)]
+    pub(crate) selected: Vec<String>,
+}
+
+impl Default for ChapterOptions {
+    fn default() -> Self {
+        ChapterOptions {
+            embed: default_embed(),
+            split: false,
+            selected: Vec::new(),
+        }
+    }
+}
+
+impl ChapterOptions {
+    /// Whether only some chapters are downloaded, as sections of the video.
+    pub(crate) fn downloads_selected(&self) -> bool {
+        self.split && !self.selected.is_empty()
+    }
+}
+
+// `--add-metadata` always embedded chapters, keep doing it unless asked not to
+fn default_embed() -> bool {
+    true
+}
+
+/// Builds the yt-dlp arguments for embedding and splitting chapters. The output template of
+/// split chapters is set by the caller, since it depends on the download folder.
+pub(crate) fn chapter_args(chapters: &ChapterOptions) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+
+    args.push(if chapters.embed {
+        "--embed-chapters".into()
+    } else {
+        "--no-embed-chapters".into()
+    });
+
+    if chapters.downloads_selected() {
+        // Sections without the "*" prefix are regexes matched against chapter titles
+        for title in &chapters.selected {
+            args.push("--download-sections".into());
+            args.push(format!("^{}$", escape(title)));
+        }
+    } else if chapters.split {
+        args.push("--split-chapters".into());
+    }
+
+    args
+}
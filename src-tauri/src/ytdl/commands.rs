@@ -1,114 +1,353 @@
 use std::sync::{Arc, Mutex};
 
-use tauri::{AppHandle, Emitter, State, Window};
-use tauri::async_runtime::spawn;
+use tauri::{AppHandle, State, Window};
 
+use crate::ytdl::archive;
+use crate::ytdl::archive::ArchiveEntry;
+use crate::ytdl::cookies::{cookies_path, import_cookies_file, CookieSource};
 use crate::ytdl::deps::{invoke_ffmpeg_from_local, invoke_ytdlp_from_local};
-use crate::ytdl::downloads::{download_video, get_video_info};
+use crate::ytdl::downloads;
+use crate::ytdl::downloads::{DownloadOptions, StopRequest};
+use crate::ytdl::formats::{get_video_formats_list, FormatInfo};
+use crate::ytdl::history;
+use crate::ytdl::history::{HistoryFilter, HistoryRecord};
+use crate::ytdl::info::{get_video_info, VideoInfo};
+use crate::ytdl::logs;
+use crate::ytdl::logs::LogFile;
+use crate::ytdl::queue::{
+    emit_queue_update, process_queue, record_cancelled, DownloadJob, DownloadQueue,
+};
+use crate::ytdl::sanitize;
+use crate::ytdl::settings::{load_settings, save_settings, Settings};
+use crate::ytdl::templates::validate_template;
+use crate::ytdl::url::{normalize_url, NormalizedUrl};
 
 #[derive(Default)]
 pub(crate) struct AppState {
-    download_in_progress: Arc<Mutex<bool>>,
+    download_queue: Arc<Mutex<DownloadQueue>>,
 }
 
+/// Fetches the details of a video, shown before downloading it.
 #[tauri::command]
 pub(crate) fn fetch_video(
     url: String,
+    cookies: Option<CookieSource>,
     handle: AppHandle,
-) -> Result<(String, String, String, String), String> {
-    let ffmpeg_path = match invoke_ffmpeg_from_local(handle.clone()) {
-        Ok(path) => path,
-        Err(e) => {
-            return Err(e);
-        }
-    };
-
-    let ytdlp_path = match invoke_ytdlp_from_local(handle) {
-        Ok(path) => path,
-        Err(e) => {
-            return Err(e);
-        }
-    };
-
-    let video_info = get_video_info(&url, &ytdlp_path, &ffmpeg_path);
-
-    match video_info {
-        Ok(video_info) => Ok((
-            video_info.title,
-            video_info.ext,
-            video_info.thumbnail,
-            video_info.uploader,
-        )),
-        Err(e) => Err(*Box::from(e.to_string())),
+) -> Result<VideoInfo, String> {
+    let url = normalize_url(&url)?;
+    let ytdlp_path = invoke_ytdlp_from_local(handle)?;
+
+    get_video_info(url.url(), &ytdlp_path, cookies.as_ref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn fetch_formats(
+    url: String,
+    cookies: Option<CookieSource>,
+    handle: AppHandle,
+) -> Result<Vec<FormatInfo>, String> {
+    let url = normalize_url(&url)?;
+    let ytdlp_path = invoke_ytdlp_from_local(handle)?;
+
+    get_video_formats_list(url.url(), &ytdlp_path, cookies.as_ref()).map_err(|e| e.to_string())
+}
+
+/// Path a download would be saved as with these options, its output template filled in from the
+/// video's fields.
+#[tauri::command]
+pub(crate) fn preview_output_path(
+    url: String,
+    options: DownloadOptions,
+    handle: AppHandle,
+) -> Result<String, String> {
+    let url = normalize_url(&url)?;
+    let ytdlp_path = invoke_ytdlp_from_local(handle)?;
+
+    downloads::preview_output_path(url.url(), &options, &ytdlp_path).map_err(|e| e.to_string())
+}
+
+/// Checks an output template without a video, for the preset editor.
+#[tauri::command]
+pub(crate) fn check_output_template(template: String) -> Result<(), String> {
+    validate_template(&template)
+}
+
+/// Makes a file name safe with the saved file name settings, e.g. for the names suggested in
+/// save dialogs.
+#[tauri::command]
+pub(crate) fn sanitize_filename(name: String) -> String {
+    let filenames = load_settings().filenames;
+    sanitize::sanitize_filename(&name, filenames.mode, filenames.max_bytes)
+}
+
+/// Checks a URL typed by the user and returns it normalized, with what it points to on YouTube.
+#[tauri::command]
+pub(crate) fn check_url(url: String) -> Result<NormalizedUrl, String> {
+    normalize_url(&url)
+}
+
+/// Imports a Netscape cookies.txt, used by downloads with the `file` cookie source.
+#[tauri::command]
+pub(crate) fn import_cookies(path: String) -> Result<(), String> {
+    import_cookies_file(&path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn has_imported_cookies() -> bool {
+    cookies_path().exists()
+}
+
+#[tauri::command]
+pub(crate) fn delete_cookies() -> Result<(), String> {
+    let path = cookies_path();
+    if path.exists() {
+        std::fs::remove_file(path).map_err(|e| e.to_string())?;
     }
+
+    Ok(())
+}
+
+/// Lists the videos in the download archive, which are skipped by new downloads.
+#[tauri::command]
+pub(crate) fn list_archive() -> Result<Vec<ArchiveEntry>, String> {
+    archive::read_archive().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn remove_from_archive(entries: Vec<ArchiveEntry>) -> Result<(), String> {
+    archive::remove_archive_entries(&entries).map_err(|e| e.to_string())
+}
+
+/// Merges another archive file into the download archive, returning how many entries were new.
+#[tauri::command]
+pub(crate) fn import_archive(path: String) -> Result<usize, String> {
+    archive::import_archive(&path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn export_archive(path: String) -> Result<(), String> {
+    archive::export_archive(&path).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub(crate) async fn download_video_command(
     url: String,
-    format: Option<String>,
-    path: String,
-    unique_folders: bool,
-    download_thumbnail: bool,
-    write_url_link: bool,
+    options: DownloadOptions,
     state: State<'_, AppState>, // Ensure the same AppState is used
     window: Window,
     handle: AppHandle,
-) -> Result<bool, String> {
-    let download_in_progress = Arc::clone(&state.download_in_progress);
-
-    {
-        let mut in_progress = download_in_progress.lock().unwrap();
-        if *in_progress {
-            return Err("Another download is already in progress".to_string());
-        }
-        *in_progress = true;
-    }
+) -> Result<u64, String> {
+    enqueue_download(url, options, &state, window, handle)
+}
+
+/// Lists the downloads that ended, newest first. Without a filter every download is listed.
+#[tauri::command]
+pub(crate) fn list_history(filter: Option<HistoryFilter>) -> Result<Vec<HistoryRecord>, String> {
+    history::list_records(&filter.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn clear_history() -> Result<(), String> {
+    history::clear_history().map_err(|e| e.to_string())
+}
 
-    let window_clone = window.clone();
-    let download_in_progress_clone = Arc::clone(&download_in_progress);
+#[tauri::command]
+pub(crate) fn list_logs() -> Vec<LogFile> {
+    logs::list_logs()
+}
+
+#[tauri::command]
+pub(crate) fn read_log(name: String) -> Result<String, String> {
+    logs::read_log(&name).map_err(|e| e.to_string())
+}
+
+/// Zips the given logs, or every log when `names` is empty, to `path`. Returns how many were
+/// added.
+#[tauri::command]
+pub(crate) fn export_logs(path: String, names: Vec<String>) -> Result<usize, String> {
+    logs::export_logs(&path, &names).map_err(|e| e.to_string())
+}
+
+/// Queues a download from the history again, with the same options. The download archive is
+/// ignored, since the video is most likely in it already.
+#[tauri::command]
+pub(crate) fn redownload(
+    record_id: u64,
+    state: State<'_, AppState>,
+    window: Window,
+    handle: AppHandle,
+) -> Result<u64, String> {
+    let record = history::get_record(record_id)?;
+    let mut options = record.options;
+    options.ignore_archive = true;
+
+    enqueue_download(record.url, options, &state, window, handle)
+}
+
+/// Adds a download to the queue and starts it if there's a free slot. Returns the job ID.
+fn enqueue_download(
+    url: String,
+    options: DownloadOptions,
+    state: &AppState,
+    window: Window,
+    handle: AppHandle,
+) -> Result<u64, String> {
+    // Bad URLs are refused here instead of failing the job later
+    let url = normalize_url(&url)?.url().to_string();
+    options.validate()?;
 
     // Get the path to the ffmpeg executable
-    let ffmpeg_path = match invoke_ffmpeg_from_local(handle.clone()) {
-        Ok(path) => path,
-        Err(e) => {
-            let mut in_progress = download_in_progress.lock().unwrap();
-            *in_progress = false;
-            return Err(e);
-        }
-    };
-
-    let ytdlp_path = match invoke_ytdlp_from_local(handle) {
-        Ok(path) => path,
-        Err(e) => {
-            let mut in_progress = download_in_progress.lock().unwrap();
-            *in_progress = false;
-            return Err(e);
-        }
-    };
-
-    spawn(async move {
-        let result = download_video(
-            &url,
-            format.as_deref(),
-            path,
-            unique_folders,
-            download_thumbnail,
-            write_url_link,
-            &ytdlp_path,
-            &ffmpeg_path,
-            &window_clone,
-        );
-        match result.await {
-            Ok(_) => window_clone.emit("download_complete", true).unwrap(),
-            Err(e) => window_clone.emit("download_error", e.to_string()).unwrap(),
-        }
-
-        let mut in_progress = download_in_progress_clone.lock().unwrap();
-        *in_progress = false;
-    });
-
-    Ok(true)
+    let ffmpeg_path = invoke_ffmpeg_from_local(handle.clone())?;
+    let ytdlp_path = invoke_ytdlp_from_local(handle)?;
+
+    let queue = Arc::clone(&state.download_queue);
+    let job_id = queue.lock().unwrap().enqueue(url, options);
+
+    emit_queue_update(&queue, &window);
+    process_queue(queue, ytdlp_path, ffmpeg_path, window);
+
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub(crate) fn list_downloads(state: State<'_, AppState>) -> Vec<DownloadJob> {
+    state.download_queue.lock().unwrap().jobs()
+}
+
+#[tauri::command]
+pub(crate) fn move_download(
+    job_id: u64,
+    position: usize,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<(), String> {
+    state
+        .download_queue
+        .lock()
+        .unwrap()
+        .move_job(job_id, position)?;
+    emit_queue_update(&state.download_queue, &window);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn remove_download(
+    job_id: u64,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<(), String> {
+    state.download_queue.lock().unwrap().remove(job_id)?;
+    emit_queue_update(&state.download_queue, &window);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn cancel_download(
+    job_id: u64,
+    delete_files: bool,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<(), String> {
+    let cancelled = state
+        .download_queue
+        .lock()
+        .unwrap()
+        .stop(job_id, StopRequest::Cancel { delete_files })?;
+    if let Some((job, control)) = cancelled {
+        record_cancelled(&window, &job, &control);
+    }
+    emit_queue_update(&state.download_queue, &window);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn pause_download(
+    job_id: u64,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<(), String> {
+    state
+        .download_queue
+        .lock()
+        .unwrap()
+        .stop(job_id, StopRequest::Pause)?;
+    emit_queue_update(&state.download_queue, &window);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn stop_recording(
+    job_id: u64,
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<(), String> {
+    state
+        .download_queue
+        .lock()
+        .unwrap()
+        .stop(job_id, StopRequest::StopRecording)?;
+    emit_queue_update(&state.download_queue, &window);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn resume_download(
+    job_id: u64,
+    state: State<'_, AppState>,
+    window: Window,
+    handle: AppHandle,
+) -> Result<(), String> {
+    state.download_queue.lock().unwrap().resume(job_id)?;
+    emit_queue_update(&state.download_queue, &window);
+
+    let ffmpeg_path = invoke_ffmpeg_from_local(handle.clone())?;
+    let ytdlp_path = invoke_ytdlp_from_local(handle)?;
+    process_queue(
+        Arc::clone(&state.download_queue),
+        ytdlp_path,
+        ffmpeg_path,
+        window,
+    );
+
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_max_concurrent_downloads(
+    limit: usize,
+    state: State<'_, AppState>,
+    window: Window,
+    handle: AppHandle,
+) -> Result<(), String> {
+    state
+        .download_queue
+        .lock()
+        .unwrap()
+        .set_max_concurrent(limit)?;
+
+    // A higher limit might let queued jobs start right away
+    let ffmpeg_path = invoke_ffmpeg_from_local(handle.clone())?;
+    let ytdlp_path = invoke_ytdlp_from_local(handle)?;
+    process_queue(
+        Arc::clone(&state.download_queue),
+        ytdlp_path,
+        ffmpeg_path,
+        window,
+    );
+
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn get_settings() -> Settings {
+    load_settings()
+}
+
+/// Saves new defaults, used by the downloads that start from now on.
+#[tauri::command]
+pub(crate) fn update_settings(settings: Settings) -> Result<(), String> {
+    settings.validate()?;
+    save_settings(&settings).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
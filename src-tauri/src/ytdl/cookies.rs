@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use winapi::um::winbase::CREATE_NO_WINDOW;
+
+use crate::ytdl::settings::config_dir;
+
+/// Browsers yt-dlp can read cookies from.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Browser {
+    Brave,
+    Chrome,
+    Chromium,
+    Edge,
+    Firefox,
+    Opera,
+    Vivaldi,
+}
+
+impl Browser {
+    fn name(&self) -> &'static str {
+        match self {
+            Browser::Brave => "brave",
+            Browser::Chrome => "chrome",
+            Browser::Chromium => "chromium",
+            Browser::Edge => "edge",
+            Browser::Firefox => "firefox",
+            Browser::Opera => "opera",
+            Browser::Vivaldi => "vivaldi",
+        }
+    }
+}
+
+/// Where yt-dlp gets the cookies for age-restricted and members-only videos.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum CookieSource {
+    /// The cookies.txt imported with `import_cookies`
+    File,
+    /// A browser profile, the default one when no profile name or path is given
+    Browser {
+        browser: Browser,
+        profile: Option<String>,
+    },
+}
+
+/// The imported cookies.txt, kept in the app config directory.
+pub(crate) fn cookies_path() -> PathBuf {
+    config_dir().join("cookies").join("cookies.txt")
+}
+
+/// Builds the yt-dlp arguments that load cookies from `source`.
+pub(crate) fn cookie_args(source: Option<&CookieSource>) -> Result<Vec<String>, String> {
+    match source {
+        None => Ok(Vec::new()),
+        Some(CookieSource::File) => {
+            let path = cookies_path();
+            if !path.exists() {
+                return Err("No cookies file was imported".into());
+            }
+
+            Ok(vec!["--cookies".into(), path.to_str().unwrap().into()])
+        }
+        Some(CookieSource::Browser { browser, profile }) => {
+            let browser = match profile {
+                Some(profile) => format!("{}:{}", browser.name(), profile),
+                None => browser.name().into(),
+            };
+
+            Ok(vec!["--cookies-from-browser".into(), browser])
+        }
+    }
+}
+
+/// Removes the cookies yt-dlp puts in an info JSON (the `cookies` of each format and its `Cookie`
+/// header), so it can be written to disk. yt-dlp adds them back from the same source when the
+/// JSON is loaded.
+pub(crate) fn strip_cookies(info: &mut Value) {
+    match info {
+        Value::Object(fields) => {
+            fields.remove("cookies");
+            if let Some(Value::Object(headers)) = fields.get_mut("http_headers") {
+                headers.retain(|name, _| !name.eq_ignore_ascii_case("cookie"));
+            }
+
+            fields.values_mut().for_each(strip_cookies);
+        }
+        Value::Array(values) => values.iter_mut().for_each(strip_cookies),
+        _ => {}
+    }
+}
+
+/// Copies a Netscape cookies.txt into the app config directory, readable only by the current user.
+pub(crate) fn import_cookies_file(source: &str) -> Result<(), Box<dyn Error>> {
+    let cookies = fs::read_to_string(source)?;
+    if !is_netscape_cookies(&cookies) {
+        return Err("The file is not a Netscape cookies.txt".into());
+    }
+
+    let path = cookies_path();
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    // Restrict the file before the cookies are written to it
+    File::create(&path)?;
+    if let Err(e) = restrict_to_current_user(&path) {
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+
+    fs::write(&path, cookies)?;
+
+    Ok(())
+}
+
+/// Removes inherited permissions from `path` and gives full control to the current user only.
+fn restrict_to_current_user(path: &Path) -> Result<(), Box<dyn Error>> {
+    let user = std::env::var("USERNAME")?;
+
+    let output = Command::new("icacls")
+        .arg(path)
+        .arg("/inheritance:r")
+        .arg("/grant:r")
+        .arg(format!("{}:F", user))
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to restrict the cookies file permissions: {}",
+            String::from_utf8_lossy(&output.stdout)
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Every line is a comment, empty, or a cookie with the 7 tab separated Netscape fields.
+fn is_netscape_cookies(cookies: &str) -> bool {
+    let mut has_cookie = false;
+
+    for line in cookies.lines() {
+        // "#HttpOnly_" prefixes cookies, not comments
+        if line.is_empty() || (line.starts_with('#') && !line.starts_with("#HttpOnly_")) {
+            continue;
+        }
+        if line.split('\t').count() != 7 {
+            return false;
+        }
+        has_cookie = true;
+    }
+
+    has_cookie
+}
+
+/// Hides the cookies file path in a line of yt-dlp output. `--verbose` prints the whole command
+/// line, with backslashes escaped.
+pub(crate) fn redact_cookies_path(line: &str, cookies_path: &str) -> String {
+    line.replace(&cookies_path.replace('\\', "\\\\"), "<cookies>")
+        .replace(cookies_path, "<cookies>")
+}
@@ -3,7 +3,6 @@ use std::fs::File;
 use std::io::{self, Cursor};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::Duration;
 
 use reqwest::blocking::Client;
 use reqwest::header::USER_AGENT;
@@ -11,10 +10,12 @@ use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
 use zip_extract::extract;
 
+use crate::ytdl::network::download_bytes;
+use crate::ytdl::settings::{config_dir, load_settings};
+
 pub fn invoke_ffmpeg_from_local(handle: AppHandle) -> Result<String, String> {
     // Resolve the resource path
-    let app_resource_path = dirs::config_dir()
-        .expect("Failed to get config directory").join("Blue Lady's Tools");;
+    let app_resource_path = config_dir();
     let resource_path = app_resource_path.join("ffmpeg/bin/ffmpeg.exe");
 
     // Execute the ffmpeg command to check if it works
@@ -41,8 +42,7 @@ pub fn invoke_ffmpeg_from_local(handle: AppHandle) -> Result<String, String> {
 
 pub fn invoke_ytdlp_from_local(handle: tauri::AppHandle) -> Result<String, String> {
     // Resolve the resource path
-    let app_resource_path = dirs::config_dir()
-        .expect("Failed to get config directory").join("Blue Lady's Tools");;
+    let app_resource_path = config_dir();
     let yt_dlp_path = app_resource_path.join("yt-dlp/yt-dlp.exe");
 
     // Execute the ytdlp command to check if it works
@@ -114,37 +114,36 @@ pub(crate) fn verify_deps(handle: AppHandle) -> VerifyDeps {
 }
 
 #[tauri::command]
-pub(crate) fn download_deps() -> DownloadDepsResponse {
-    // Create a custom client with a longer timeout
-    let client = Client::builder()
-        .timeout(Duration::from_secs(600)) // Set timeout to 600 seconds (10 minutes)
+pub(crate) fn download_deps() -> Result<DownloadDepsResponse, String> {
+    let network = load_settings().network;
+
+    // The downloads can take long on slow or rate limited connections, only a stalled read times out
+    let client = network
+        .apply_to_client(Client::builder())
+        .map_err(|e| e.to_string())?
         .build()
-        .expect("Failed to build client");
+        .map_err(|e| e.to_string())?;
 
     // FFMPEG URL
     let ffmpeg_url = "https://github.com/yt-dlp/FFmpeg-Builds/releases/latest/download/ffmpeg-master-latest-win64-gpl.zip"; //<-- I hope this doesn't change...
     let ytdlp_url = "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe";
 
-    let app_resource_path = dirs::config_dir()
-        .expect("Failed to get config directory").join("Blue Lady's Tools");
+    let app_resource_path = config_dir();
     let ffmpeg_zip_path = app_resource_path.join("ffmpeg.zip");
     let ffmpeg_extracted_path = app_resource_path.join("ffmpeg");
-    fs::create_dir_all(&ffmpeg_extracted_path).expect("Failed to create FFMPEG directory");
+    fs::create_dir_all(&ffmpeg_extracted_path).map_err(|e| e.to_string())?;
 
     // Download FFMPEG
-    let ffmpeg_response = client
+    let ffmpeg_request = client
         .get(ffmpeg_url)
-        .header(USER_AGENT, "reqwest")
-        .send()
-        .expect("Failed to download FFMPEG");
-    let ffmpeg_bytes = ffmpeg_response
-        .bytes()
-        .expect("Failed to read FFMPEG bytes");
+        .header(USER_AGENT, "reqwest");
+    let ffmpeg_bytes = download_bytes(ffmpeg_request, &network)
+        .map_err(|e| format!("Failed to download FFMPEG: {}", e))?;
     let mut ffmpeg_out =
         File::create(
             &ffmpeg_zip_path
-        ).expect("Failed to create FFMPEG file");
-    io::copy(&mut ffmpeg_bytes.as_ref(), &mut ffmpeg_out).expect("Failed to write FFMPEG bytes");
+        ).map_err(|e| e.to_string())?;
+    io::copy(&mut ffmpeg_bytes.as_slice(), &mut ffmpeg_out).map_err(|e| e.to_string())?;
 
 
     extract(
@@ -152,13 +151,12 @@ pub(crate) fn download_deps() -> DownloadDepsResponse {
         &ffmpeg_extracted_path,
         false,
     )
-        .expect("Failed to extract FFMPEG");
+        .map_err(|e| format!("Failed to extract FFMPEG: {}", e))?;
 
     let extracted_path = fs::read_dir(&ffmpeg_extracted_path)
-        .expect("Failed to read extracted directory")
+        .map_err(|e| e.to_string())?
         .filter_map(|entry| {
-            let entry = entry.expect("Failed to read directory entry");
-            let path = entry.path();
+            let path = entry.ok()?.path();
             if path.is_dir() {
                 Some(path)
             } else {
@@ -166,43 +164,42 @@ pub(crate) fn download_deps() -> DownloadDepsResponse {
             }
         })
         .next()
-        .expect("Failed to find extracted directory");
+        .ok_or("Failed to find extracted directory")?;
 
     let target_path = Path::new(&ffmpeg_extracted_path);
 
     // Move all files from extracted_path to target_path
-    for entry in fs::read_dir(extracted_path).expect("Failed to read extracted directory") {
-        let entry = entry.expect("Failed to read entry");
+    for entry in fs::read_dir(extracted_path).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
         let dest = target_path.join(entry.file_name());
-        fs::rename(entry.path(), dest).expect("Failed to move file");
+        fs::rename(entry.path(), dest).map_err(|e| e.to_string())?;
     }
 
     let yt_dlp_path = app_resource_path.join("yt-dlp");
-    fs::create_dir_all(&yt_dlp_path).expect("Failed to create YT-DLP directory");
+    fs::create_dir_all(&yt_dlp_path).map_err(|e| e.to_string())?;
     let yt_dlp_exe_path = yt_dlp_path.join("yt-dlp.exe");
 
     // Download YT-DLP
-    let ytdlp_response = client
+    let ytdlp_request = client
         .get(ytdlp_url)
         .header(USER_AGENT, "reqwest")
-        .header("Accept", "application/octet-stream")
-        .send()
-        .expect("Failed to download YT-DLP");
-    let ytdlp_bytes = ytdlp_response.bytes().expect("Failed to read YT-DLP bytes");
+        .header("Accept", "application/octet-stream");
+    let ytdlp_bytes = download_bytes(ytdlp_request, &network)
+        .map_err(|e| format!("Failed to download YT-DLP: {}", e))?;
     let mut ytdlp_out =
-        File::create(&yt_dlp_exe_path).expect("Failed to create YT-DLP file");
-    io::copy(&mut ytdlp_bytes.as_ref(), &mut ytdlp_out).expect("Failed to write YT-DLP bytes");
+        File::create(&yt_dlp_exe_path).map_err(|e| e.to_string())?;
+    io::copy(&mut ytdlp_bytes.as_slice(), &mut ytdlp_out).map_err(|e| e.to_string())?;
 
     // Cleanup
-    fs::remove_file(&ffmpeg_zip_path).expect("Failed to remove FFMPEG zip file");
+    fs::remove_file(&ffmpeg_zip_path).map_err(|e| e.to_string())?;
 
     let ffmpeg_extracted_dir = find_extracted_ffmpeg_dir(&ffmpeg_extracted_path.to_str().unwrap());
     if let Some(ffmpeg_extracted_dir) = ffmpeg_extracted_dir {
         fs::remove_dir_all(ffmpeg_extracted_dir)
-            .expect("Failed to remove extracted FFMPEG directory");
+            .map_err(|e| e.to_string())?;
     }
 
-    DownloadDepsResponse {
+    Ok(DownloadDepsResponse {
         ffmpeg: Deps {
             success: true,
             message: "FFMPEG downloaded successfully".to_string(),
@@ -211,7 +208,7 @@ pub(crate) fn download_deps() -> DownloadDepsResponse {
             success: true,
             message: "YT-DLP downloaded successfully".to_string(),
         },
-    }
+    })
 }
 
 fn find_extracted_ffmpeg_dir(base_path: &str) -> Option<PathBuf> {
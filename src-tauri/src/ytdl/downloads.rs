@@ -1,27 +1,57 @@
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fs;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::os::windows::process::CommandExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use tauri::{Emitter, Window};
 use tokio::sync::mpsc;
 use winapi::um::winbase::CREATE_NO_WINDOW;
 
-#[derive(Debug, PartialEq)]
-enum VideoType {
-    Clip,
-    Playlist, // <-- Will not support it yet due to complexity
-    Livestream,
-    Video,
-}
+use crate::ytdl::archive::{archive_args, archived_ids};
+use crate::ytdl::chapters::{
+    chapter_args, ChapterOptions, CHAPTER_FILE_TEMPLATE, SELECTED_CHAPTER_FILE_TEMPLATE,
+};
+use crate::ytdl::cookies::{
+    cookie_args, cookies_path, redact_cookies_path, strip_cookies, CookieSource,
+};
+use crate::ytdl::errors::DownloadError;
+use crate::ytdl::formats::{
+    audio_args, container_args, AudioOptions, Container, ContainerPolicy, FormatSelection,
+};
+use crate::ytdl::info::{
+    fetch_playlist_entry_info, fetch_video_info, get_video_info, VideoInfo, VideoType,
+};
+use crate::ytdl::live::{finalize_recording, live_args, watch_recording, LiveOptions};
+use crate::ytdl::logs::create_job_log;
+use crate::ytdl::metadata::{metadata_args, MetadataOptions};
+use crate::ytdl::network::network_args;
+use crate::ytdl::progress::{
+    progress_template_args, DownloadProgress, ProgressParser, ProgressUpdate,
+};
+use crate::ytdl::retry::wait_before_retry;
+use crate::ytdl::sanitize::{filename_args, sanitize_folder_name, FilenameSettings};
+use crate::ytdl::sections::{section_args, SectionOptions, SECTION_FILE_TEMPLATE};
+use crate::ytdl::settings::{config_dir, load_settings, Settings};
+use crate::ytdl::sponsorblock::{sponsorblock_args, SponsorBlockOptions};
+use crate::ytdl::subtitles::{subtitle_args, SubtitleOptions};
+use crate::ytdl::templates::validate_template;
+use crate::ytdl::thumbnails::{prefer_largest_thumbnail, thumbnail_args, ThumbnailOptions};
+
+/// How many of the last output lines of yt-dlp are kept to classify its errors.
+const ERROR_CONTEXT_LINES: usize = 50;
 
 #[derive(Debug, PartialEq)]
 enum VideoFormats {
-    AudioOnly,     // mp3
+    AudioOnly,     // mp3 unless another audio format is chosen
     VideoOnly,     // mp4
     VideoAndAudio, // Selected by default
 }
@@ -35,36 +65,379 @@ struct DownloadStructure {
     formats: Option<VideoFormats>,
 }
 
-#[derive(PartialEq, Debug)]
-pub(crate) struct VideoInfo {
-    pub(crate) title: String,
-    pub(crate) ext: String,
-    pub(crate) thumbnail: String,
-    pub(crate) uploader: String,
+/// Options sent by the frontend for a download job.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadOptions {
+    pub(crate) format: Option<String>,
+    pub(crate) path: String,
+    pub(crate) unique_folders: bool,
+    pub(crate) download_thumbnail: bool,
+    pub(crate) write_url_link: bool,
+    /// Exact format or quality/codec preference, replaces the default format of `format`
+    #[serde(default)]
+    pub(crate) format_selection: Option<FormatSelection>,
+    #[serde(default)]
+    pub(crate) container: Container,
+    #[serde(default)]
+    pub(crate) container_policy: ContainerPolicy,
+    /// Format and quality of audio only downloads
+    #[serde(default)]
+    pub(crate) audio: AudioOptions,
+    /// How livestreams are recorded
+    #[serde(default)]
+    pub(crate) live: LiveOptions,
+    /// Parts of the video to download instead of the whole video
+    #[serde(default)]
+    pub(crate) sections: SectionOptions,
+    #[serde(default)]
+    pub(crate) subtitles: SubtitleOptions,
+    /// Embedding chapter markers and splitting the video by chapters
+    #[serde(default)]
+    pub(crate) chapters: ChapterOptions,
+    /// SponsorBlock action per category, the saved defaults are used when not set
+    #[serde(default)]
+    pub(crate) sponsorblock: Option<SponsorBlockOptions>,
+    /// Cookies for age-restricted and members-only videos
+    #[serde(default)]
+    pub(crate) cookies: Option<CookieSource>,
+    /// Download even if the video is in the download archive, without archiving it again
+    #[serde(default)]
+    pub(crate) ignore_archive: bool,
+    /// yt-dlp output template relative to `path`, replaces the file name that fits the type of
+    /// video and the unique folder
+    #[serde(default)]
+    pub(crate) output_template: Option<String>,
+    /// Embedding, converting and picking the thumbnail
+    #[serde(default)]
+    pub(crate) thumbnail: ThumbnailOptions,
+    /// Which metadata fields are written and what they're filled with
+    #[serde(default)]
+    pub(crate) metadata: MetadataOptions,
 }
 
-fn get_video_type(url: &str) -> Result<VideoType, Box<dyn Error>> {
-    let patterns = vec![
-        (r"https?://(www\.)?youtube\.com/clip/", VideoType::Clip),
-        (
-            r"https?://(www\.)?youtube\.com/playlist\?list=",
-            VideoType::Playlist,
-        ),
-        (
-            r"https?://(www\.)?youtube\.com/watch\?v=[^&]+&live",
-            VideoType::Livestream,
-        ),
-        (r"https?://(www\.)?youtube\.com/watch\?v=", VideoType::Video),
-    ];
+impl DownloadOptions {
+    /// Checks the options that would only make yt-dlp fail once the download is done.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if let Some(template) = &self.output_template {
+            validate_template(template)?;
+        }
+
+        self.thumbnail.check_embed(self.file_ext())?;
+        self.metadata.validate()
+    }
+
+    /// Extension of the downloaded file, `None` when yt-dlp keeps the one of the format.
+    fn file_ext(&self) -> Option<&'static str> {
+        match get_video_formats(self.format.as_deref()) {
+            VideoFormats::AudioOnly => self.audio.format.ext(),
+            _ => self.container.ext(),
+        }
+    }
+
+    /// Output file name template, relative to the download folder.
+    fn file_template(&self) -> &'static str {
+        if !self.sections.is_empty() {
+            SECTION_FILE_TEMPLATE
+        } else if self.chapters.downloads_selected() {
+            SELECTED_CHAPTER_FILE_TEMPLATE
+        } else {
+            "%(title)s.%(ext)s"
+        }
+    }
+
+    /// yt-dlp `--output` arguments for downloads saved in `folder`, named by the output template
+    /// or with file names that fit the type of video.
+    fn output_args(&self, folder: &Path, video_type: VideoType) -> Vec<String> {
+        // Templates are validated to end with the extension
+        let name = match &self.output_template {
+            Some(template) => template.strip_suffix(".%(ext)s").unwrap_or(template),
+            None => video_type.name_template(),
+        };
+        let mut args: Vec<String> = vec![
+            "--output".into(),
+            folder
+                .join(self.file_template().replace("%(title)s", name))
+                .to_str()
+                .unwrap()
+                .into(),
+        ];
+
+        // Split chapters have their own template, which is relative to the working directory
+        // unless it's a full path
+        if self.chapters.split && !self.chapters.downloads_selected() {
+            args.push("--output".into());
+            args.push(format!(
+                "chapter:{}",
+                folder
+                    .join(CHAPTER_FILE_TEMPLATE.replace("%(title)s", name))
+                    .to_str()
+                    .unwrap()
+            ));
+        }
+
+        args
+    }
+
+    /// Folder a single video is saved in, named after the video when `unique_folders` is on.
+    fn video_folder(&self, video_info: &VideoInfo, filenames: &FilenameSettings) -> PathBuf {
+        let mut folder = PathBuf::from(&self.path);
+
+        // Output templates choose their own folders
+        if self.unique_folders && self.output_template.is_none() {
+            folder.push(sanitize_folder_name(
+                &video_info.folder_name(),
+                filenames.mode,
+                filenames.max_bytes,
+            ));
+        }
+
+        folder
+    }
+}
+
+/// Why a running download was asked to stop.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum StopRequest {
+    Cancel {
+        delete_files: bool,
+    },
+    Pause,
+    /// Ends a livestream recording, keeping what was recorded so far
+    StopRecording,
+}
+
+/// Shared handle to a running download, used to stop its yt-dlp process from a command.
+#[derive(Default)]
+pub(crate) struct JobControl {
+    pid: Mutex<Option<u32>>,
+    stop_request: Mutex<Option<StopRequest>>,
+    destinations: Mutex<Vec<PathBuf>>,
+    /// Files yt-dlp found already downloaded, which this job didn't create and never deletes
+    existing_files: Mutex<Vec<PathBuf>>,
+    /// Info of the video being downloaded, kept for the download history
+    video_info: Mutex<Option<VideoInfo>>,
+}
+
+impl JobControl {
+    /// Records the stop request and kills the yt-dlp process tree, if one is running.
+    pub(crate) fn stop(&self, request: StopRequest) {
+        *self.stop_request.lock().unwrap() = Some(request);
+
+        if let Some(pid) = *self.pid.lock().unwrap() {
+            kill_process_tree(pid);
+        }
+    }
+
+    pub(crate) fn stop_request(&self) -> Option<StopRequest> {
+        *self.stop_request.lock().unwrap()
+    }
+
+    pub(crate) fn destinations(&self) -> Vec<PathBuf> {
+        self.destinations.lock().unwrap().clone()
+    }
+
+    pub(crate) fn video_info(&self) -> Option<VideoInfo> {
+        self.video_info.lock().unwrap().clone()
+    }
+
+    /// Handle for the next attempt of a paused job, which still knows the files of the previous
+    /// attempts.
+    pub(crate) fn resumed(&self) -> JobControl {
+        JobControl {
+            destinations: Mutex::new(self.destinations()),
+            existing_files: Mutex::new(self.existing_files.lock().unwrap().clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Files yt-dlp wrote or found already downloaded for this job that are still on disk, which
+    /// are the final files once it ended (intermediate files are deleted after merging or
+    /// converting).
+    pub(crate) fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        let existing_files = self.existing_files.lock().unwrap().clone();
+
+        for destination in self.destinations().into_iter().chain(existing_files) {
+            if destination.is_file() && !files.contains(&destination) {
+                files.push(destination);
+            }
+        }
+
+        files
+    }
+
+    /// Deletes the files of this job that never finished: `.part`, `.ytdl`, fragment and `.temp`
+    /// files, and the format files of a merge that didn't happen. Finished files, like the videos
+    /// of a playlist that were done before it was cancelled, are kept.
+    pub(crate) fn delete_partial_files(&self) {
+        let destinations = self.destinations();
+        let existing_files = self.existing_files.lock().unwrap().clone();
+
+        for destination in &destinations {
+            let (Some(parent), Some(file_name)) = (destination.parent(), destination.file_name())
+            else {
+                continue;
+            };
+            let file_name = file_name.to_string_lossy();
+            let temp_name = temp_file_name(destination);
+            let unfinished_format = is_unfinished_format_file(destination, &destinations);
+
+            let Ok(entries) = fs::read_dir(parent) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let is_partial = name == temp_name
+                    || name.strip_prefix(file_name.as_ref()).is_some_and(|suffix| {
+                        matches!(suffix, ".part" | ".ytdl" | ".temp")
+                            || suffix.starts_with(".part-Frag")
+                    })
+                    || (unfinished_format && name == file_name);
+
+                if is_partial && !existing_files.contains(&entry.path()) {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+
+    /// Keeps track of the files yt-dlp reports writing to.
+    fn record_destination(&self, line: &str) {
+        if let Some(existing_file) = line
+            .strip_prefix("[download] ")
+            .and_then(|rest| rest.strip_suffix(" has already been downloaded"))
+        {
+            self.existing_files
+                .lock()
+                .unwrap()
+                .push(PathBuf::from(existing_file.trim()));
+            return;
+        }
+
+        // Post-processors report their output as "[ExtractAudio] Destination: ..." or
+        // "[VideoRemuxer] Remuxing video from webm to mp4; Destination: ..."
+        let destination = line
+            .strip_prefix("[download] Destination: ")
+            .or_else(|| {
+                line.strip_prefix("[Merger] Merging formats into \"")
+                    .and_then(|rest| rest.strip_suffix('"'))
+            })
+            .or_else(|| {
+                line.starts_with('[')
+                    .then(|| line.rsplit_once("Destination: "))
+                    .flatten()
+                    .map(|(_, destination)| destination)
+            });
 
-    for (pattern, video_type) in patterns {
-        let re = Regex::new(pattern)?;
-        if re.is_match(url) {
-            return Ok(video_type);
+        if let Some(destination) = destination {
+            self.destinations
+                .lock()
+                .unwrap()
+                .push(PathBuf::from(destination.trim()));
         }
     }
+}
+
+/// Name of the file ffmpeg writes before replacing `destination` with it (`title.temp.mp4`).
+fn temp_file_name(destination: &Path) -> String {
+    let stem = destination
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    match destination.extension() {
+        Some(extension) => format!("{}.temp.{}", stem, extension.to_string_lossy()),
+        None => format!("{}.temp", stem),
+    }
+}
+
+/// The path of a format file without its format ID and extension (`title.f299.mp4` gives
+/// `title`), `None` for other files.
+fn format_file_stem(path: &Path) -> Option<PathBuf> {
+    let format_file = Regex::new(r"^(.+)\.f[0-9A-Za-z_-]+\.[^.]+$").unwrap();
+    let path = path.to_string_lossy();
 
-    Err("Invalid URL format".into())
+    format_file
+        .captures(&path)
+        .map(|captures| PathBuf::from(&captures[1]))
+}
+
+/// Whether `destination` is one of the formats of a merge that didn't finish. Once the merged
+/// file exists, yt-dlp deletes the format files itself.
+fn is_unfinished_format_file(destination: &Path, destinations: &[PathBuf]) -> bool {
+    let Some(stem) = format_file_stem(destination) else {
+        return false;
+    };
+
+    let formats = destinations
+        .iter()
+        .filter(|other| format_file_stem(other).as_ref() == Some(&stem))
+        .count();
+    let merged = destinations
+        .iter()
+        .any(|other| other.with_extension("") == stem && other.is_file());
+
+    formats > 1 && !merged
+}
+
+/// Everything a running download needs besides the URL and its options.
+pub(crate) struct DownloadContext<'a> {
+    pub(crate) job_id: u64,
+    pub(crate) ytdlp_path: &'a str,
+    pub(crate) ffmpeg_path: &'a str,
+    pub(crate) window: &'a Window,
+    pub(crate) control: &'a Arc<JobControl>,
+}
+
+/// A single entry of a playlist, as listed by `--flat-playlist`.
+#[derive(Debug)]
+struct PlaylistEntry {
+    index: usize,
+    title: String,
+    /// `extractor id`, as in the download archive
+    archive_id: Option<String>,
+    video_type: VideoType,
+}
+
+/// Payload emitted on `playlist_progress` while a playlist is being downloaded.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlaylistProgress {
+    pub(crate) job_id: u64,
+    pub(crate) index: usize,
+    pub(crate) total: usize,
+    pub(crate) title: String,
+    pub(crate) progress: ProgressUpdate,
+}
+
+/// Payload emitted on `playlist_entry_error` when a single entry fails to download.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlaylistEntryError {
+    pub(crate) job_id: u64,
+    pub(crate) index: usize,
+    pub(crate) total: usize,
+    pub(crate) title: String,
+    pub(crate) error: DownloadError,
+}
+
+/// Payload emitted on `playlist_entry_retrying` when a single entry failed for a transient reason
+/// and will be tried again.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlaylistEntryRetrying {
+    pub(crate) job_id: u64,
+    pub(crate) index: usize,
+    pub(crate) total: usize,
+    pub(crate) title: String,
+    /// The attempt about to start, 2 for the first retry
+    pub(crate) attempt: u32,
+    pub(crate) max_attempts: u32,
+    /// Seconds until the attempt starts
+    pub(crate) delay: f64,
+    pub(crate) error: DownloadError,
 }
 
 fn get_video_formats(user_format: Option<&str>) -> VideoFormats {
@@ -75,197 +448,612 @@ fn get_video_formats(user_format: Option<&str>) -> VideoFormats {
     }
 }
 
-pub(crate) async fn download_video(
-    url: &str,
-    format: Option<&str>,
-    path: String,
-    unique_folders: bool,
-    download_thumbnail: bool,
-    write_url_link: bool,
-    ytdlp_path: &str,
+/// Builds the yt-dlp arguments shared by single video and playlist downloads.
+/// Output path and URL are added by the caller.
+fn build_ytdlp_args(
+    options: &DownloadOptions,
+    settings: &Settings,
     ffmpeg_path: &str,
-    window: &Window,
-) -> Result<bool, Box<dyn Error>> {
-    let video_type = get_video_type(url).unwrap();
-    let formats = get_video_formats(format);
-
-    if video_type == VideoType::Playlist {
-        return Err("Playlist download is not supported yet".into());
-    }
-
+) -> Vec<String> {
     let mut ytdlp_args: Vec<String> = Vec::new();
+    let selection = options.format_selection.as_ref();
 
     // Check which format to download and set the appropriate flags
-    match formats {
+    match get_video_formats(options.format.as_deref()) {
         VideoFormats::AudioOnly => {
-            ytdlp_args.push("--extract-audio".into());
-            ytdlp_args.push("--audio-format".into());
-            ytdlp_args.push("mp3".into());
-            // Needs to be added to the args if the format is audio only
+            ytdlp_args.extend(audio_args(&options.audio, selection));
         }
         VideoFormats::VideoOnly => {
             ytdlp_args.push("--format".into());
-            ytdlp_args.push("bestvideo[ext=mp4]".into());
+            ytdlp_args.push(match selection {
+                Some(selection) => selection.selector(false),
+                None if options.container == Container::Mp4 => "bestvideo[ext=mp4]".into(),
+                None => "bestvideo".into(),
+            });
+            ytdlp_args.extend(container_args(options.container, options.container_policy));
         }
         VideoFormats::VideoAndAudio => {
-            // This downloads the best video with audio as a mp4 file. Might edit this later to support other formats (if supported by yt-dlp)
+            // This downloads the best video with audio, merged into the chosen container
             ytdlp_args.push("--format".into());
-            ytdlp_args.push("bv+ba".into());
-
-            // Force the output format to mp4.
-            // This might cause issues, I don't know if I'm going to keep this.
-            // Might set it as an option in the future and let the user decide.
-            ytdlp_args.push("--merge-output-format".into());
-            ytdlp_args.push("mp4".into());
+            ytdlp_args.push(match selection {
+                Some(selection) => selection.selector(true),
+                None => "bv+ba".into(),
+            });
+            ytdlp_args.extend(container_args(options.container, options.container_policy));
         }
     }
 
     ytdlp_args.push("--ffmpeg-location".into());
     ytdlp_args.push(ffmpeg_path.into());
+    ytdlp_args.extend(network_args(&settings.network));
+    ytdlp_args.extend(filename_args(&settings.filenames));
 
+    if !options.ignore_archive {
+        ytdlp_args.extend(archive_args());
+    }
 
+    ytdlp_args.extend(thumbnail_args(
+        options.download_thumbnail,
+        &options.thumbnail,
+    ));
 
-    let video_info =
-        get_video_info(url, &ytdlp_path, ffmpeg_path).expect("Failed to get video info");
+    if options.write_url_link {
+        ytdlp_args.push("--write-url-link".into());
+    }
 
-    let mut output_path = PathBuf::from(path);
+    ytdlp_args.extend(subtitle_args(&options.subtitles));
+    ytdlp_args.extend(chapter_args(&options.chapters));
+    ytdlp_args.extend(sponsorblock_args(
+        options
+            .sponsorblock
+            .as_ref()
+            .unwrap_or(&settings.sponsorblock.categories),
+        settings.sponsorblock.api_url.as_deref(),
+    ));
 
-    if unique_folders && video_type != VideoType::Playlist {
-        output_path.push(&video_info.title);
-    }
+    ytdlp_args.extend(metadata_args(&options.metadata));
+    // Paused downloads are resumed from their .part files
+    ytdlp_args.push("--continue".into());
+    ytdlp_args.push("--progress".into());
+    ytdlp_args.extend(progress_template_args());
+    ytdlp_args.push("--newline".into());
+    ytdlp_args.push("--verbose".into());
+
+    ytdlp_args
+}
+
+pub(crate) async fn download_video(
+    ctx: &DownloadContext<'_>,
+    url: &str,
+    options: &DownloadOptions,
+) -> Result<bool, Box<dyn Error>> {
+    let (job_id, ytdlp_path, ffmpeg_path) = (ctx.job_id, ctx.ytdlp_path, ctx.ffmpeg_path);
+    let settings = load_settings();
+    options.validate()?;
+
+    let mut ytdlp_args = build_ytdlp_args(options, &settings, ffmpeg_path);
+    ytdlp_args.extend(section_args(&options.sections)?);
+    ytdlp_args.extend(cookie_args(options.cookies.as_ref())?);
+
+    // yt-dlp tells which site and type of video the URL is
+    let (video_info, mut info_json) =
+        match fetch_video_info(url, ytdlp_path, options.cookies.as_ref()) {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                // The error is the only thing there is to log
+                if let Ok(mut log) = create_job_log("unknown", &settings.logs) {
+                    let cookies_path = cookies_path().to_string_lossy().into_owned();
+                    let error = redact_cookies_path(&e.to_string(), &cookies_path);
+                    let _ = writeln!(log, "{}\n{}", url, error);
+                }
+                return Err(e);
+            }
+        };
+    let mut ytdlp_log = create_job_log(&video_info.id, &settings.logs)?;
+    let video_type = video_info.video_type;
+    let video_folder = options.video_folder(&video_info, &settings.filenames);
+    *ctx.control.video_info.lock().unwrap() = Some(video_info);
 
-    // I might not support playlist downloads yet, but I will keep this here for future reference
     if video_type == VideoType::Playlist {
-        output_path.push("%(playlist)s");
-        output_path.push("%(title)s.%(ext)s");
-    } else {
-        output_path.push("%(title)s.%(ext)s");
+        return download_playlist(ctx, url, options, ytdlp_args, &mut ytdlp_log).await;
     }
 
     // This sets the output path for the video. Frontend handles retrieving the default path if a custom path is not set.
-    ytdlp_args.push("--output".into());
-    ytdlp_args.push(output_path.to_str().unwrap().into());
+    ytdlp_args.extend(options.output_args(&video_folder, video_type));
+    // Video URLs with a playlist in them (`watch?v=...&list=...`) only download the video
+    ytdlp_args.push("--no-playlist".into());
 
-    if download_thumbnail {
-        ytdlp_args.push("--write-thumbnail".into());
+    if video_type == VideoType::Livestream {
+        // The recording can't be started from the info, its formats go stale and a stream that
+        // hasn't begun has none, so the largest thumbnail is written on its own
+        if options.thumbnail.largest && options.download_thumbnail {
+            write_largest_thumbnail(ctx, options, &video_folder, &mut info_json, &mut ytdlp_log)
+                .await?;
+            ytdlp_args.retain(|arg| arg != "--write-thumbnail");
+        }
+
+        ytdlp_args.extend(live_args(&options.live));
+        ytdlp_args.push(url.into());
+        return record_livestream(ctx, &ytdlp_args, &mut ytdlp_log).await;
     }
 
-    if write_url_link {
-        ytdlp_args.push("--write-url-link".into());
+    let info_path = if options.thumbnail.largest {
+        let info_path = write_ranked_info_json(job_id, &mut info_json)?;
+        ytdlp_args.push("--load-info-json".into());
+        ytdlp_args.push(info_path.to_str().unwrap().into());
+        Some(info_path)
+    } else {
+        ytdlp_args.push(url.into());
+        None
+    };
+
+    let mut parser = ProgressParser::new();
+    let result = run_ytdlp(ctx, &ytdlp_args, &mut ytdlp_log, |line| {
+        if let Some(progress) = parser.parse_line(line) {
+            ctx.window
+                .emit("download_progress", DownloadProgress { job_id, progress })
+                .unwrap();
+        }
+    })
+    .await;
+
+    if let Some(info_path) = info_path {
+        let _ = fs::remove_file(info_path);
+    }
+
+    result
+}
+
+/// Writes the info JSON of a download with its thumbnails ranked by size, to be given back to
+/// yt-dlp with `--load-info-json` since it has no option to pick the largest thumbnail. It goes
+/// without its cookies to the `info` folder in the app config directory, where it's kept until
+/// the download ends.
+fn write_ranked_info_json(
+    job_id: u64,
+    info: &mut serde_json::Value,
+) -> Result<PathBuf, Box<dyn Error>> {
+    prefer_largest_thumbnail(info);
+    strip_cookies(info);
+
+    let dir = config_dir().join("info");
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!("{}.info.json", job_id));
+    fs::write(&path, serde_json::to_vec(info)?)?;
+
+    Ok(path)
+}
+
+/// Path of the file a download would be saved as, with its output template filled in by yt-dlp
+/// from the video's fields. For playlists, the path of the first entry.
+pub(crate) fn preview_output_path(
+    url: &str,
+    options: &DownloadOptions,
+    ytdlp_path: &str,
+) -> Result<String, Box<dyn Error>> {
+    options.validate()?;
+
+    let settings = load_settings();
+    let video_info = get_video_info(url, ytdlp_path, options.cookies.as_ref())?;
+    let video_type = video_info.video_type;
+    let output_args = if video_type == VideoType::Playlist {
+        let mut args = options.output_args(
+            &PathBuf::from(&options.path).join("%(playlist)s"),
+            VideoType::Video,
+        );
+        args.push("--playlist-items".into());
+        args.push("1".into());
+        args
+    } else {
+        let folder = options.video_folder(&video_info, &settings.filenames);
+        let mut args = options.output_args(&folder, video_type);
+        args.push("--no-playlist".into());
+        args
+    };
+
+    let output = Command::new(ytdlp_path)
+        .args(cookie_args(options.cookies.as_ref())?)
+        .args(network_args(&settings.network))
+        .args(filename_args(&settings.filenames))
+        .args(output_args)
+        .arg("--skip-download")
+        .arg("--print")
+        .arg("filename")
+        .arg(url)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(DownloadError::from_output(&String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|path| path.trim().to_string())
+        .ok_or_else(|| "yt-dlp printed no output path".into())
+}
+
+/// Writes the largest thumbnail of a livestream next to where it will be recorded, with a
+/// yt-dlp run that loads its ranked info and skips the download.
+async fn write_largest_thumbnail(
+    ctx: &DownloadContext<'_>,
+    options: &DownloadOptions,
+    video_folder: &Path,
+    info_json: &mut serde_json::Value,
+    ytdlp_log: &mut File,
+) -> Result<bool, Box<dyn Error>> {
+    let settings = load_settings();
+    let info_path = write_ranked_info_json(ctx.job_id, info_json)?;
+
+    let mut ytdlp_args = cookie_args(options.cookies.as_ref())?;
+    ytdlp_args.extend(network_args(&settings.network));
+    ytdlp_args.extend(filename_args(&settings.filenames));
+    ytdlp_args.extend(options.output_args(video_folder, VideoType::Livestream));
+    ytdlp_args.push("--ffmpeg-location".into());
+    ytdlp_args.push(ctx.ffmpeg_path.into());
+    ytdlp_args.extend(thumbnail_args(
+        true,
+        &ThumbnailOptions {
+            embed: false,
+            ..options.thumbnail.clone()
+        },
+    ));
+    ytdlp_args.push("--skip-download".into());
+    ytdlp_args.push("--load-info-json".into());
+    ytdlp_args.push(info_path.to_str().unwrap().into());
+
+    let result = run_ytdlp(ctx, &ytdlp_args, ytdlp_log, |_| {}).await;
+    let _ = fs::remove_file(info_path);
+
+    result
+}
+
+/// Records a livestream until it ends or `stop_recording` is called. Progress is reported by
+/// `watch_recording` instead of yt-dlp's output, which has no percent for a livestream.
+async fn record_livestream(
+    ctx: &DownloadContext<'_>,
+    ytdlp_args: &[String],
+    ytdlp_log: &mut File,
+) -> Result<bool, Box<dyn Error>> {
+    let done = watch_recording(ctx.job_id, Arc::clone(ctx.control), ctx.window.clone());
+    let result = run_ytdlp(ctx, ytdlp_args, ytdlp_log, |_| {}).await;
+    done.store(true, Ordering::Relaxed);
+
+    // yt-dlp is killed to stop a recording, so its exit code doesn't mean it failed
+    if ctx.control.stop_request() == Some(StopRequest::StopRecording) {
+        return finalize_recording(ctx.control, ctx.ffmpeg_path);
+    }
+
+    result
+}
+
+/// Downloads every entry of a playlist into `<path>/<playlist title>/`, one yt-dlp run per entry.
+///
+/// Entries are downloaded through the playlist URL with `--playlist-items` so the `%(playlist)s`
+/// and `%(playlist_index)s` fields are filled in. A failed entry is reported on
+/// `playlist_entry_error` and the remaining entries keep going; the whole download only fails
+/// when no entry could be downloaded. Entries in the download archive are skipped, so running a
+/// playlist again only downloads its new entries.
+async fn download_playlist(
+    ctx: &DownloadContext<'_>,
+    url: &str,
+    options: &DownloadOptions,
+    ytdlp_args: Vec<String>,
+    ytdlp_log: &mut File,
+) -> Result<bool, Box<dyn Error>> {
+    let (job_id, window) = (ctx.job_id, ctx.window);
+    let mut entries = get_playlist_entries(url, ctx.ytdlp_path, options.cookies.as_ref())?;
+
+    if entries.is_empty() {
+        return Err("Playlist has no entries".into());
+    }
+
+    if !options.ignore_archive {
+        let archived = archived_ids()?;
+        entries.retain(
+            |entry| !matches!(&entry.archive_id, Some(archive_id) if archived.contains(archive_id)),
+        );
+    }
+
+    let total = entries.len();
+
+    // Nothing new since the last time
+    if total == 0 {
+        return Ok(true);
+    }
+
+    let mut output_folder = PathBuf::from(&options.path);
+    output_folder.push("%(playlist)s");
+
+    let retry = load_settings().retry;
+    let mut failed = 0;
+    let mut last_error: Option<DownloadError> = None;
+
+    for (position, entry) in entries.iter().enumerate() {
+        let mut entry_args = ytdlp_args.clone();
+        entry_args.extend(options.output_args(&output_folder, entry.video_type));
+
+        let mut attempt = 1;
+        let result = loop {
+            // The entry's info is fetched again for every attempt, the URLs in it expire
+            let result = match playlist_entry_source(ctx, url, entry, options)
+                .map_err(DownloadError::from_boxed)
+            {
+                Ok((source_args, info_path)) => {
+                    let mut attempt_args = entry_args.clone();
+                    attempt_args.extend(source_args);
+
+                    let mut parser = ProgressParser::new();
+                    let result = run_ytdlp(ctx, &attempt_args, ytdlp_log, |line| {
+                        if let Some(progress) = parser.parse_line(line) {
+                            window
+                                .emit(
+                                    "playlist_progress",
+                                    PlaylistProgress {
+                                        job_id,
+                                        index: position + 1,
+                                        total,
+                                        title: entry.title.clone(),
+                                        progress,
+                                    },
+                                )
+                                .unwrap();
+                        }
+                    })
+                    .await
+                    .map_err(DownloadError::from_boxed);
+
+                    if let Some(info_path) = info_path {
+                        let _ = fs::remove_file(info_path);
+                    }
+                    result
+                }
+                Err(error) => Err(error),
+            };
+            let error = match result {
+                Ok(done) => break Ok(done),
+                Err(error) => error,
+            };
+
+            if !retry.should_retry(&error, attempt) || ctx.control.stop_request().is_some() {
+                break Err(error);
+            }
+
+            let delay = retry.delay(attempt);
+            attempt += 1;
+            window
+                .emit(
+                    "playlist_entry_retrying",
+                    PlaylistEntryRetrying {
+                        job_id,
+                        index: position + 1,
+                        total,
+                        title: entry.title.clone(),
+                        attempt,
+                        max_attempts: retry.max_attempts,
+                        delay: delay.as_secs_f64(),
+                        error: error.clone(),
+                    },
+                )
+                .unwrap();
+
+            wait_before_retry(ctx.control, delay).await;
+        };
+
+        // A paused or cancelled playlist doesn't move on to the next entry
+        if ctx.control.stop_request().is_some() {
+            return result.map_err(Into::into);
+        }
+
+        if let Err(error) = result {
+            failed += 1;
+            window
+                .emit(
+                    "playlist_entry_error",
+                    PlaylistEntryError {
+                        job_id,
+                        index: position + 1,
+                        total,
+                        title: entry.title.clone(),
+                        error: error.clone(),
+                    },
+                )
+                .unwrap();
+            last_error = Some(error);
+        }
+    }
+
+    // Entries usually fail for the same reason, like a network error or rate limiting
+    if failed == total {
+        let error = last_error.expect("Every entry failed with an error");
+        return Err(DownloadError::new(
+            error.code,
+            format!(
+                "All {} playlist entries failed to download, the last one with:\n{}",
+                total, error.details
+            ),
+        )
+        .into());
     }
 
-    if url.contains("clip") {
-        //Check if ffmpeg was already added to args
-        if !ytdlp_args.contains(&"--ffmpeg-location".to_string()) {
-            ytdlp_args.push("--ffmpeg-location".into());
-            ytdlp_args.push(ffmpeg_path.into());
+    Ok(true)
+}
+
+/// The arguments that tell yt-dlp which entry of the playlist to download, and the info JSON they
+/// load, if any. Entries are picked through the playlist URL, unless the largest thumbnail is
+/// wanted: their info is then fetched and loaded with the thumbnails ranked by size. Live entries
+/// are still recorded through the playlist URL, the info of a stream goes stale.
+fn playlist_entry_source(
+    ctx: &DownloadContext<'_>,
+    url: &str,
+    entry: &PlaylistEntry,
+    options: &DownloadOptions,
+) -> Result<(Vec<String>, Option<PathBuf>), Box<dyn Error>> {
+    if options.thumbnail.largest && entry.video_type != VideoType::Livestream {
+        let mut info_json =
+            fetch_playlist_entry_info(url, entry.index, ctx.ytdlp_path, options.cookies.as_ref())?;
+        let info_path = write_ranked_info_json(ctx.job_id, &mut info_json)?;
+        let args = vec![
+            "--load-info-json".into(),
+            info_path.to_str().unwrap().into(),
+        ];
+        return Ok((args, Some(info_path)));
+    }
+
+    let args = vec![
+        "--playlist-items".into(),
+        entry.index.to_string(),
+        url.into(),
+    ];
+    Ok((args, None))
+}
+
+/// Lists the entries of a playlist without downloading them.
+fn get_playlist_entries(
+    url: &str,
+    ytdlp_path: &str,
+    cookies: Option<&CookieSource>,
+) -> Result<Vec<PlaylistEntry>, Box<dyn Error>> {
+    let output = Command::new(ytdlp_path)
+        .args(cookie_args(cookies)?)
+        .args(network_args(&load_settings().network))
+        .arg("--flat-playlist")
+        .arg("--print")
+        .arg("%(playlist_index)s\t%(ie_key)s\t%(id)s\t%(title)s")
+        .arg(url)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(DownloadError::from_output(&String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let entries = stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            let (index, extractor, id, title) = (
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+                fields.next()?,
+            );
+
+            // Same ID yt-dlp writes to the archive, unknown fields are printed as "NA"
+            let archive_id = (extractor != "NA" && id != "NA")
+                .then(|| format!("{} {}", extractor.to_lowercase(), id));
+
+            Some(PlaylistEntry {
+                index: index.trim().parse().ok()?,
+                title: title.to_string(),
+                archive_id,
+                video_type: VideoType::from_extractor(extractor),
+            })
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+/// Sends every line of `output` to `tx` until it ends. Lines that aren't valid UTF-8 (titles in
+/// the console's code page) are converted lossily, the pipe has to be read to the end or yt-dlp
+/// blocks once it's full.
+fn forward_lines(output: impl Read, tx: mpsc::Sender<String>) {
+    for line in BufReader::new(output).split(b'\n').map_while(Result::ok) {
+        let line = String::from_utf8_lossy(&line)
+            .trim_end_matches('\r')
+            .to_string();
+        if tx.blocking_send(line).is_err() {
+            break;
         }
     }
+}
 
-    ytdlp_args.push("--add-metadata".into());
-    ytdlp_args.push("--progress".into());
-    ytdlp_args.push("--newline".into());
-    ytdlp_args.push("--verbose".into());
-    ytdlp_args.push(url.into());
+/// Runs yt-dlp with the given arguments, writing every output line to the log and handing it to `on_line`.
+///
+/// The process ID is published on the job's `JobControl` while yt-dlp runs, so the download can be
+/// stopped from outside.
+async fn run_ytdlp<F>(
+    ctx: &DownloadContext<'_>,
+    ytdlp_args: &[String],
+    ytdlp_log: &mut File,
+    mut on_line: F,
+) -> Result<bool, Box<dyn Error>>
+where
+    F: FnMut(&str),
+{
+    if ctx.control.stop_request().is_some() {
+        return Err("Download was stopped".into());
+    }
 
-    let mut process = Command::new(ytdlp_path)
-        .args(&ytdlp_args)
+    let mut process = Command::new(ctx.ytdlp_path)
+        .args(ytdlp_args)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .creation_flags(CREATE_NO_WINDOW)
         .spawn()
-        .expect("Failed to start yt-dlp");
+        .map_err(|e| format!("Failed to start yt-dlp: {}", e))?;
+
+    *ctx.control.pid.lock().unwrap() = Some(process.id());
+
+    // The download might have been stopped while yt-dlp was starting
+    if ctx.control.stop_request().is_some() {
+        kill_process_tree(process.id());
+    }
 
     let stdout = process.stdout.take().expect("Failed to capture stdout");
     let stderr = process.stderr.take().expect("Failed to capture stderr");
 
     let (tx, mut rx) = mpsc::channel::<String>(100);
 
-    // Read stdout in a separate thread
+    // Read stdout and stderr in separate threads. The reads block, so they can't hold one of the
+    // runtime's workers for as long as the download runs.
     let tx_clone = tx.clone();
-    tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            tx_clone.send(line).await.unwrap();
-        }
-    });
-
-    // Read stderr in a separate thread
-    tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            tx.send(line).await.unwrap();
-        }
-    });
-
-    let app_resource_path = dirs::config_dir()
-        .expect("Failed to get config directory").join("Blue Lady's Tools");
-    let ytdlp_log_path = app_resource_path.join("logs");
+    thread::spawn(move || forward_lines(stdout, tx_clone));
+    thread::spawn(move || forward_lines(stderr, tx));
 
-    // Create the ytdlp.log file
-    fs::create_dir_all(&ytdlp_log_path).expect("Failed to create FFMPEG directory");
-    let ytdlp_log_path = ytdlp_log_path.join("ytdlp.log");
-    File::create(&ytdlp_log_path).expect("Failed to create ytdlp.log");
-
-    let mut ytdlp_log = OpenOptions::new()
-        .write(true)
-        .open(&ytdlp_log_path)
-        .unwrap();
+    let cookies_path = cookies_path().to_string_lossy().into_owned();
+    // The end of the output, where yt-dlp reports why it failed
+    let mut last_lines: VecDeque<String> = VecDeque::with_capacity(ERROR_CONTEXT_LINES);
 
     // Process the output lines
     while let Some(line) = rx.recv().await {
-        window.emit("download_progress", line.clone()).unwrap();
+        ctx.control.record_destination(&line);
+        on_line(&line);
+
+        if last_lines.len() == ERROR_CONTEXT_LINES {
+            last_lines.pop_front();
+        }
+        last_lines.push_back(line.clone());
 
-        // Write ytdlp.log to the resources folder for debugging purposes with new lines for each log entry
-        ytdlp_log.write_all(format!("{}\n", line).as_bytes()).unwrap();
+        // Write every line to the job's log for debugging purposes
+        ytdlp_log
+            .write_all(format!("{}\n", redact_cookies_path(&line, &cookies_path)).as_bytes())
+            .unwrap();
     }
 
-    match process.wait_with_output() {
+    let result = process.wait_with_output();
+    *ctx.control.pid.lock().unwrap() = None;
+
+    match result {
         Ok(output) => {
             if output.status.success() {
                 Ok(true)
             } else {
-                Err(format!("Error downloading video: Stdout {:?}", output.stdout).into())
+                let output = Vec::from(last_lines).join("\n");
+                Err(DownloadError::from_output(&output).into())
             }
         }
         Err(e) => Err(e.to_string().into()),
     }
 }
 
-pub(crate) fn get_video_info(
-    url: &str,
-    ytdlp_path: &str,
-    ffmpeg_path: &str,
-) -> Result<VideoInfo, Box<dyn Error>> {
-    let video_info = Command::new(ytdlp_path)
-        .arg("--print")
-        .arg("title")
-        .arg("--print")
-        .arg("ext")
-        .arg("--print")
-        .arg("thumbnail")
-        .arg("--print")
-        .arg("uploader")
-        .arg("--ffmpeg-location")
-        .arg(ffmpeg_path)
-        .arg(url)
+/// Kills a process and all of its children (yt-dlp spawns ffmpeg for merging and fragments).
+fn kill_process_tree(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
         .creation_flags(CREATE_NO_WINDOW)
-        .output()
-        .expect("Failed to get video info");
-
-    if !video_info.status.success() {
-        return Err("Failed to get video info".into());
-    }
-
-    let video_info = String::from_utf8_lossy(&video_info.stdout);
-    let video_info = video_info.split("\n").collect::<Vec<&str>>();
-
-    Ok(VideoInfo {
-        title: video_info[0].to_string(),
-        ext: video_info[1].to_string(),
-        thumbnail: video_info[2].to_string(),
-        uploader: video_info[3].to_string(),
-    })
+        .output();
 }
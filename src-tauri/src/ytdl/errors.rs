@@ -0,0 +1,404 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Why yt-dlp failed, classified from its error output so the UI can suggest a fix.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum YtdlpError {
+    PrivateVideo,
+    VideoRemoved,
+    GeoBlocked,
+    /// Age-restricted videos and YouTube's "confirm you're not a bot" check
+    SignInRequired,
+    MembersOnly,
+    PremiereNotStarted,
+    /// HTTP 429
+    RateLimited,
+    NetworkError,
+    /// Merging or converting with ffmpeg failed
+    FfmpegFailed,
+    /// The site changed and yt-dlp has to be updated
+    ExtractorOutdated,
+    DiskFull,
+    Unknown,
+}
+
+impl YtdlpError {
+    /// Classifies yt-dlp's output, from its `ERROR:` lines when there are some.
+    pub(crate) fn classify(output: &str) -> YtdlpError {
+        let output = output.to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|pattern| output.contains(pattern));
+
+        // The most specific errors come first, e.g. private videos also ask to sign in
+        if has(&[
+            "no space left on device",
+            "not enough space on the disk",
+            "[errno 28]",
+            "[winerror 112]",
+        ]) {
+            YtdlpError::DiskFull
+        } else if has(&[
+            "ffmpeg not found",
+            "ffmpeg exited with code",
+            "postprocessing:",
+            "conversion failed",
+            "error merging",
+        ]) {
+            YtdlpError::FfmpegFailed
+        } else if has(&["http error 429", "too many requests"]) {
+            YtdlpError::RateLimited
+        } else if has(&[
+            "premieres in",
+            "premiere will begin",
+            "live event will begin",
+            "this live event will begin",
+        ]) {
+            YtdlpError::PremiereNotStarted
+        } else if has(&[
+            "members-only",
+            "members only",
+            "join this channel",
+            "available to this channel's members",
+        ]) {
+            YtdlpError::MembersOnly
+        } else if has(&["private video", "this video is private"]) {
+            YtdlpError::PrivateVideo
+        } else if has(&[
+            "sign in to confirm",
+            "age-restricted",
+            "age restricted",
+            "inappropriate for some users",
+            "login required",
+            "use --cookies",
+        ]) {
+            YtdlpError::SignInRequired
+        } else if has(&[
+            "not available in your country",
+            "not made this video available in your country",
+            "geo restricted",
+            "geo-restricted",
+            "georestricted",
+        ]) {
+            YtdlpError::GeoBlocked
+        } else if has(&[
+            // Fragments and media URLs fail with HTTP errors of their own, which don't mean the
+            // video is gone. A 403 is usually an expired media URL, a new attempt gets new ones.
+            "unable to download fragment",
+            "fragment retries",
+            "not found, unable to continue",
+            "did not get any data blocks",
+            "unable to download video data",
+            "http error 403",
+        ]) {
+            YtdlpError::NetworkError
+        } else if has(&[
+            "video unavailable",
+            "has been removed",
+            "no longer available",
+            "account associated with this video has been terminated",
+            "http error 404",
+            "http error 410",
+        ]) {
+            YtdlpError::VideoRemoved
+        } else if has(&[
+            "unable to extract",
+            "nsig extraction failed",
+            "signature extraction failed",
+            "please report this issue",
+            "update to the latest version",
+        ]) {
+            YtdlpError::ExtractorOutdated
+        } else if has(&[
+            "unable to download webpage",
+            "unable to download json metadata",
+            "timed out",
+            "connection reset",
+            "connection refused",
+            "connection aborted",
+            "remote end closed connection",
+            "network is unreachable",
+            "getaddrinfo failed",
+            "name or service not known",
+            "temporary failure in name resolution",
+            "ssl:",
+            "unable to connect to proxy",
+            "incompleteread",
+            "http error 500",
+            "http error 502",
+            "http error 503",
+            "http error 504",
+        ]) {
+            YtdlpError::NetworkError
+        } else {
+            YtdlpError::Unknown
+        }
+    }
+
+    /// Whether the same download may work if it's tried again a bit later.
+    pub(crate) fn is_transient(&self) -> bool {
+        matches!(self, YtdlpError::RateLimited | YtdlpError::NetworkError)
+    }
+
+    pub(crate) fn message(&self) -> &'static str {
+        match self {
+            YtdlpError::PrivateVideo => {
+                "This video is private. Import the cookies of an account that can watch it."
+            }
+            YtdlpError::VideoRemoved => "This video was removed or doesn't exist.",
+            YtdlpError::GeoBlocked => {
+                "This video isn't available in your country. Set a geo-bypass country or a proxy in the settings."
+            }
+            YtdlpError::SignInRequired => {
+                "YouTube asks to sign in (age restriction or bot check). Import cookies or use a browser's cookies."
+            }
+            YtdlpError::MembersOnly => {
+                "This video is for channel members. Use the cookies of an account with a membership."
+            }
+            YtdlpError::PremiereNotStarted => {
+                "This premiere or livestream hasn't started. Turn on waiting for the start and try again."
+            }
+            YtdlpError::RateLimited => {
+                "The site is limiting requests (HTTP 429). Wait a while, lower the rate limit or use cookies."
+            }
+            YtdlpError::NetworkError => {
+                "The connection failed. Check your internet connection and proxy settings."
+            }
+            YtdlpError::FfmpegFailed => {
+                "ffmpeg failed to merge or convert the download. Verify the dependencies or pick another container."
+            }
+            YtdlpError::ExtractorOutdated => {
+                "yt-dlp couldn't read this site, it's probably outdated. Update the dependencies."
+            }
+            YtdlpError::DiskFull => "The disk is full. Free some space or pick another download folder.",
+            YtdlpError::Unknown => "The download failed.",
+        }
+    }
+}
+
+/// A failed download or yt-dlp call, sent to the frontend as `{code, message, details}`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadError {
+    pub(crate) code: YtdlpError,
+    pub(crate) message: String,
+    /// yt-dlp's error, or the app's own error for failures that aren't yt-dlp's
+    pub(crate) details: String,
+}
+
+impl DownloadError {
+    pub(crate) fn new(code: YtdlpError, details: String) -> DownloadError {
+        DownloadError {
+            code,
+            message: code.message().into(),
+            details,
+        }
+    }
+
+    /// Classifies the output of a yt-dlp run that exited with an error.
+    pub(crate) fn from_output(output: &str) -> DownloadError {
+        let errors: Vec<&str> = output
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("ERROR:"))
+            .collect();
+
+        // Without an ERROR line, the end of the output is the closest thing to an error
+        if errors.is_empty() {
+            let last_lines: Vec<&str> = output
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .rev()
+                .take(5)
+                .collect();
+
+            return DownloadError::new(
+                YtdlpError::classify(&last_lines.join("\n")),
+                last_lines.first().copied().unwrap_or_default().to_string(),
+            );
+        }
+
+        let details = errors.join("\n");
+        DownloadError::new(YtdlpError::classify(&details), details)
+    }
+
+    /// The typed error behind a boxed one, or an `Unknown` error with its text.
+    pub(crate) fn from_boxed(error: Box<dyn Error>) -> DownloadError {
+        match error.downcast::<DownloadError>() {
+            Ok(error) => *error,
+            Err(error) => DownloadError::new(YtdlpError::Unknown, error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.details.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}\n{}", self.message, self.details)
+        }
+    }
+}
+
+impl Error for DownloadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(output: &str) -> YtdlpError {
+        DownloadError::from_output(output).code
+    }
+
+    #[test]
+    fn classifies_private_videos() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Private video. Sign in if you've been granted access to this video"),
+            YtdlpError::PrivateVideo
+        );
+    }
+
+    #[test]
+    fn classifies_removed_videos() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Video unavailable. This video has been removed by the uploader"),
+            YtdlpError::VideoRemoved
+        );
+        assert_eq!(
+            classify("ERROR: [generic] Unable to download webpage: HTTP Error 404: Not Found (caused by <HTTPError 404: Not Found>)"),
+            YtdlpError::VideoRemoved
+        );
+    }
+
+    #[test]
+    fn classifies_geo_blocked_videos() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: The uploader has not made this video available in your country"),
+            YtdlpError::GeoBlocked
+        );
+    }
+
+    #[test]
+    fn classifies_sign_in_requests() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm your age. This video may be inappropriate for some users. Use --cookies-from-browser or --cookies for the authentication."),
+            YtdlpError::SignInRequired
+        );
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you’re not a bot. Use --cookies-from-browser or --cookies for the authentication."),
+            YtdlpError::SignInRequired
+        );
+    }
+
+    #[test]
+    fn classifies_members_only_videos() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Join this channel to get access to members-only content like this video, and other exclusive perks."),
+            YtdlpError::MembersOnly
+        );
+    }
+
+    #[test]
+    fn classifies_premieres_that_did_not_start() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Premieres in 2 hours"),
+            YtdlpError::PremiereNotStarted
+        );
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: This live event will begin in 3 hours."),
+            YtdlpError::PremiereNotStarted
+        );
+    }
+
+    #[test]
+    fn classifies_rate_limits() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Unable to download webpage: HTTP Error 429: Too Many Requests (caused by <HTTPError 429: Too Many Requests>)"),
+            YtdlpError::RateLimited
+        );
+    }
+
+    #[test]
+    fn classifies_network_errors() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Unable to download webpage: <urlopen error [Errno 11001] getaddrinfo failed> (caused by TransportError('<urlopen error [Errno 11001] getaddrinfo failed>'))"),
+            YtdlpError::NetworkError
+        );
+        assert_eq!(
+            classify("ERROR: unable to download video data: HTTP Error 403: Forbidden"),
+            YtdlpError::NetworkError
+        );
+    }
+
+    #[test]
+    fn classifies_fragment_failures_as_network_errors() {
+        let output =
+            "[download] Got error: HTTP Error 404: Not Found. Retrying fragment 7 (10/10)...\n\
+                      ERROR: fragment 7 not found, unable to continue";
+        assert_eq!(classify(output), YtdlpError::NetworkError);
+
+        // Without an ERROR line, the retries at the end of the output are classified
+        let output = "[download] Got error: HTTP Error 404: Not Found. Retrying fragment 7 (3/10)...\n\
+                      [download] Got error: HTTP Error 410: Gone. Giving up after 10 fragment retries";
+        assert_eq!(classify(output), YtdlpError::NetworkError);
+    }
+
+    #[test]
+    fn classifies_ffmpeg_failures() {
+        assert_eq!(
+            classify("ERROR: Postprocessing: Conversion failed!"),
+            YtdlpError::FfmpegFailed
+        );
+        assert_eq!(
+            classify("ERROR: ffmpeg not found. Please install or provide the path using --ffmpeg-location"),
+            YtdlpError::FfmpegFailed
+        );
+    }
+
+    #[test]
+    fn classifies_outdated_extractors() {
+        assert_eq!(
+            classify("ERROR: [youtube] dQw4w9WgXcQ: Unable to extract uploader id; please report this issue on  https://github.com/yt-dlp/yt-dlp/issues?q= , filling out the appropriate issue template. Confirm you are on the latest version using  yt-dlp -U"),
+            YtdlpError::ExtractorOutdated
+        );
+    }
+
+    #[test]
+    fn classifies_full_disks() {
+        assert_eq!(
+            classify("ERROR: unable to write data: [Errno 28] No space left on device"),
+            YtdlpError::DiskFull
+        );
+    }
+
+    #[test]
+    fn classifies_other_errors_as_unknown() {
+        assert_eq!(
+            classify("ERROR: 'NoneType' object has no attribute 'get'"),
+            YtdlpError::Unknown
+        );
+    }
+
+    #[test]
+    fn keeps_only_error_lines_as_details() {
+        let error = DownloadError::from_output(
+            "[youtube] Extracting URL: https://www.youtube.com/watch?v=dQw4w9WgXcQ\n\
+             ERROR: [youtube] dQw4w9WgXcQ: Premieres in 2 hours",
+        );
+        assert_eq!(
+            error.details,
+            "ERROR: [youtube] dQw4w9WgXcQ: Premieres in 2 hours"
+        );
+    }
+
+    #[test]
+    fn only_network_errors_and_rate_limits_are_transient() {
+        assert!(YtdlpError::NetworkError.is_transient());
+        assert!(YtdlpError::RateLimited.is_transient());
+        assert!(!YtdlpError::VideoRemoved.is_transient());
+        assert!(!YtdlpError::Unknown.is_transient());
+    }
+}
@@ -0,0 +1,327 @@
+use std::error::Error;
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use winapi::um::winbase::CREATE_NO_WINDOW;
+
+use crate::ytdl::cookies::{cookie_args, CookieSource};
+use crate::ytdl::errors::DownloadError;
+use crate::ytdl::network::network_args;
+use crate::ytdl::settings::load_settings;
+
+/// A format as reported by `yt-dlp -J`. Only the fields the app uses are kept.
+#[derive(Deserialize, Debug)]
+pub(crate) struct YtdlpFormat {
+    format_id: String,
+    format_note: Option<String>,
+    ext: Option<String>,
+    resolution: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<f64>,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    tbr: Option<f64>,
+    filesize: Option<u64>,
+    filesize_approx: Option<u64>,
+    dynamic_range: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct YtdlpFormats {
+    #[serde(default)]
+    formats: Vec<YtdlpFormat>,
+}
+
+/// A downloadable format of a video, sent to the frontend.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FormatInfo {
+    pub(crate) format_id: String,
+    pub(crate) note: Option<String>,
+    pub(crate) ext: Option<String>,
+    pub(crate) resolution: Option<String>,
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    pub(crate) fps: Option<f64>,
+    /// `None` for audio only formats
+    pub(crate) vcodec: Option<String>,
+    /// `None` for video only formats
+    pub(crate) acodec: Option<String>,
+    /// Total bitrate in KBit/s
+    pub(crate) bitrate: Option<f64>,
+    /// Exact size if known, yt-dlp's estimate otherwise
+    pub(crate) filesize: Option<u64>,
+    pub(crate) hdr: bool,
+}
+
+impl From<YtdlpFormat> for FormatInfo {
+    fn from(format: YtdlpFormat) -> Self {
+        // yt-dlp uses "none" for a missing stream
+        let codec = |codec: Option<String>| codec.filter(|codec| codec != "none");
+        let hdr = matches!(&format.dynamic_range, Some(range) if range != "SDR");
+
+        FormatInfo {
+            format_id: format.format_id,
+            note: format.format_note,
+            ext: format.ext,
+            resolution: format.resolution,
+            width: format.width,
+            height: format.height,
+            fps: format.fps,
+            vcodec: codec(format.vcodec),
+            acodec: codec(format.acodec),
+            bitrate: format.tbr,
+            filesize: format.filesize.or(format.filesize_approx),
+            hdr,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum VideoCodec {
+    Av1,
+    Vp9,
+    H265,
+    H264,
+}
+
+impl VideoCodec {
+    /// yt-dlp format filter matching this codec.
+    fn filter(&self) -> &'static str {
+        match self {
+            VideoCodec::Av1 => "[vcodec^=av01]",
+            VideoCodec::Vp9 => "[vcodec~='^vp0?9']",
+            VideoCodec::H265 => "[vcodec~='^(hev|hvc)1']",
+            VideoCodec::H264 => "[vcodec^=avc1]",
+        }
+    }
+}
+
+/// Which format(s) to download, instead of the defaults of the chosen `VideoFormats`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum FormatSelection {
+    /// A format ID from `fetch_formats`, or several joined with `+` (e.g. `137+140`)
+    #[serde(rename_all = "camelCase")]
+    Exact { format_id: String },
+    /// The best format within the limits, trying the codecs in order
+    #[serde(rename_all = "camelCase")]
+    Preference {
+        max_height: Option<u32>,
+        #[serde(default)]
+        codecs: Vec<VideoCodec>,
+    },
+}
+
+impl FormatSelection {
+    /// Builds the value for yt-dlp's `--format`. With `with_audio` the best audio is merged in.
+    pub(crate) fn selector(&self, with_audio: bool) -> String {
+        let (max_height, codecs) = match self {
+            FormatSelection::Exact { format_id } => return format_id.clone(),
+            FormatSelection::Preference { max_height, codecs } => (max_height, codecs),
+        };
+
+        let height = max_height
+            .map(|height| format!("[height<={}]", height))
+            .unwrap_or_default();
+        let audio = if with_audio { "+ba" } else { "" };
+
+        let mut selectors: Vec<String> = codecs
+            .iter()
+            .map(|codec| format!("bv{}{}{}", height, codec.filter(), audio))
+            .collect();
+
+        // Any codec, and as a last resort a format that already has both streams
+        selectors.push(format!("bv{}{}", height, audio));
+        if with_audio {
+            selectors.push(format!("b{}", height));
+        }
+
+        selectors.join("/")
+    }
+}
+
+/// Container of downloads with video. Audio only downloads are not affected.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Container {
+    #[default]
+    Mp4,
+    Mkv,
+    Webm,
+    /// Whatever container yt-dlp ends up with, nothing is remuxed
+    Original,
+}
+
+impl Container {
+    pub(crate) fn ext(&self) -> Option<&'static str> {
+        match self {
+            Container::Mp4 => Some("mp4"),
+            Container::Mkv => Some("mkv"),
+            Container::Webm => Some("webm"),
+            Container::Original => None,
+        }
+    }
+}
+
+/// What to do when the downloaded streams don't fit the chosen container as they are.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ContainerPolicy {
+    /// Only copy the streams, falling back to MKV (which takes any codec) instead of re-encoding
+    #[default]
+    Remux,
+    /// Re-encode the streams if needed to get the chosen container
+    Recode,
+}
+
+/// Builds the yt-dlp arguments that put the download in `container`.
+pub(crate) fn container_args(container: Container, policy: ContainerPolicy) -> Vec<String> {
+    let Some(ext) = container.ext() else {
+        return Vec::new();
+    };
+
+    match policy {
+        ContainerPolicy::Remux => {
+            // yt-dlp takes the first container that fits the merged codecs
+            let merge_formats = if container == Container::Mkv {
+                ext.to_string()
+            } else {
+                format!("{}/mkv", ext)
+            };
+            // Single file downloads are remuxed only when the source is not in the container yet,
+            // MP4 sources (H.264) can't go losslessly into WebM so they go to MKV
+            let remux_rules = match container {
+                Container::Webm => "mp4>mkv/webm".to_string(),
+                _ => ext.to_string(),
+            };
+
+            vec![
+                "--merge-output-format".into(),
+                merge_formats,
+                "--remux-video".into(),
+                remux_rules,
+            ]
+        }
+        ContainerPolicy::Recode => vec![
+            "--merge-output-format".into(),
+            ext.into(),
+            "--recode-video".into(),
+            ext.into(),
+        ],
+    }
+}
+
+/// Output format of audio only downloads.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AudioFormat {
+    #[default]
+    Mp3,
+    Opus,
+    M4a,
+    Aac,
+    Flac,
+    Wav,
+    /// The best audio stream as it is, without transcoding
+    Best,
+}
+
+impl AudioFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Opus => "opus",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Aac => "aac",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Best => "best",
+        }
+    }
+
+    /// Extension of the converted files, `None` when the audio is kept as it is.
+    pub(crate) fn ext(&self) -> Option<&'static str> {
+        match self {
+            AudioFormat::Best => None,
+            _ => Some(self.name()),
+        }
+    }
+
+    /// Format selector preferring a source that needs no transcoding for this format.
+    fn preferred_source(&self) -> &'static str {
+        match self {
+            AudioFormat::Opus => "ba[acodec=opus]/ba/b",
+            AudioFormat::M4a | AudioFormat::Aac => "ba[ext=m4a]/ba/b",
+            _ => "ba/b",
+        }
+    }
+}
+
+/// Quality of transcoded audio, ignored when nothing is transcoded.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum AudioQuality {
+    /// Constant bitrate in KBit/s
+    Bitrate { kbps: u32 },
+    /// VBR quality, from 0 (best) to 10 (worst)
+    Vbr { quality: u8 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AudioOptions {
+    #[serde(default)]
+    pub(crate) format: AudioFormat,
+    pub(crate) quality: Option<AudioQuality>,
+}
+
+/// Builds the yt-dlp arguments for an audio only download.
+pub(crate) fn audio_args(audio: &AudioOptions, selection: Option<&FormatSelection>) -> Vec<String> {
+    let mut args: Vec<String> = vec!["--format".into()];
+    args.push(match selection {
+        Some(FormatSelection::Exact { format_id }) => format_id.clone(),
+        _ => audio.format.preferred_source().into(),
+    });
+
+    args.push("--extract-audio".into());
+    args.push("--audio-format".into());
+    args.push(audio.format.name().into());
+
+    if let Some(quality) = audio.quality {
+        args.push("--audio-quality".into());
+        args.push(match quality {
+            AudioQuality::Bitrate { kbps } => format!("{}K", kbps),
+            AudioQuality::Vbr { quality } => quality.min(10).to_string(),
+        });
+    }
+
+    args
+}
+
+/// Lists every format available for a video, using yt-dlp's JSON output.
+pub(crate) fn get_video_formats_list(
+    url: &str,
+    ytdlp_path: &str,
+    cookies: Option<&CookieSource>,
+) -> Result<Vec<FormatInfo>, Box<dyn Error>> {
+    let output = Command::new(ytdlp_path)
+        .args(cookie_args(cookies)?)
+        .args(network_args(&load_settings().network))
+        .arg("--dump-single-json")
+        .arg("--no-playlist")
+        .arg(url)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(DownloadError::from_output(&String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    let info: YtdlpFormats = serde_json::from_slice(&output.stdout)?;
+
+    Ok(info.formats.into_iter().map(FormatInfo::from).collect())
+}
@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ytdl::downloads::DownloadOptions;
+use crate::ytdl::errors::YtdlpError;
+use crate::ytdl::settings::config_dir;
+
+/// Jobs can end at the same time, the history file is rewritten by one of them at a time.
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+/// How a download ended. Paused downloads haven't ended and are not in the history.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DownloadOutcome {
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// A download that ended, saved in `history.json` in the app config directory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoryRecord {
+    pub(crate) id: u64,
+    pub(crate) url: String,
+    /// The playlist's ID for playlists, `None` when the video info couldn't be fetched
+    pub(crate) video_id: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) uploader: Option<String>,
+    pub(crate) options: DownloadOptions,
+    /// Files of the download still on disk when it ended
+    pub(crate) files: Vec<PathBuf>,
+    /// Total size of `files` in bytes
+    pub(crate) size: u64,
+    /// Length of the video in seconds
+    pub(crate) duration: Option<f64>,
+    pub(crate) started_at: DateTime<Utc>,
+    pub(crate) finished_at: DateTime<Utc>,
+    pub(crate) outcome: DownloadOutcome,
+    pub(crate) error: Option<String>,
+    /// Why the download failed, missing from records saved before errors were classified
+    #[serde(default)]
+    pub(crate) error_code: Option<YtdlpError>,
+}
+
+/// Filters for `list_history`. Every field that is set has to match.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoryFilter {
+    /// Case-insensitive text searched in the title, uploader and URL
+    pub(crate) search: Option<String>,
+    pub(crate) outcome: Option<DownloadOutcome>,
+    /// Only downloads started at or after this time
+    pub(crate) since: Option<DateTime<Utc>>,
+    /// Only downloads started before this time
+    pub(crate) until: Option<DateTime<Utc>>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, record: &HistoryRecord) -> bool {
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            let found = [
+                record.title.as_deref(),
+                record.uploader.as_deref(),
+                Some(record.url.as_str()),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&search));
+
+            if !found {
+                return false;
+            }
+        }
+
+        self.outcome.is_none_or(|outcome| record.outcome == outcome)
+            && self.since.is_none_or(|since| record.started_at >= since)
+            && self.until.is_none_or(|until| record.started_at < until)
+    }
+}
+
+fn history_path() -> PathBuf {
+    config_dir().join("history.json")
+}
+
+/// Every record, oldest first. A history that can't be read is an error instead of an empty
+/// history, so it's not overwritten by the next download.
+fn read_history() -> Result<Vec<HistoryRecord>, Box<dyn Error>> {
+    let history = match fs::read_to_string(history_path()) {
+        Ok(history) => history,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read the download history: {}", e).into()),
+    };
+
+    serde_json::from_str(&history).map_err(|e| {
+        format!(
+            "The download history is corrupt, clear it to start over: {}",
+            e
+        )
+        .into()
+    })
+}
+
+fn write_history(history: &[HistoryRecord]) -> Result<(), Box<dyn Error>> {
+    let path = history_path();
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, serde_json::to_string_pretty(history)?)?;
+
+    Ok(())
+}
+
+/// Adds a record to the history. Its ID is replaced by the next free one.
+pub(crate) fn add_record(mut record: HistoryRecord) -> Result<(), Box<dyn Error>> {
+    let _lock = HISTORY_LOCK.lock().unwrap();
+
+    let mut history = read_history()?;
+    record.id = history.iter().map(|record| record.id).max().unwrap_or(0) + 1;
+    history.push(record);
+
+    write_history(&history)
+}
+
+/// The records matching `filter`, newest first.
+pub(crate) fn list_records(filter: &HistoryFilter) -> Result<Vec<HistoryRecord>, Box<dyn Error>> {
+    let _lock = HISTORY_LOCK.lock().unwrap();
+
+    let mut records: Vec<HistoryRecord> = read_history()?
+        .into_iter()
+        .filter(|record| filter.matches(record))
+        .collect();
+    records.reverse();
+
+    Ok(records)
+}
+
+pub(crate) fn get_record(id: u64) -> Result<HistoryRecord, String> {
+    let _lock = HISTORY_LOCK.lock().unwrap();
+
+    read_history()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|record| record.id == id)
+        .ok_or_else(|| format!("No download with ID {} in the history", id))
+}
+
+pub(crate) fn clear_history() -> Result<(), Box<dyn Error>> {
+    let _lock = HISTORY_LOCK.lock().unwrap();
+
+    write_history(&[])
+}
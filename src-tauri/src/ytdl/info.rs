@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use winapi::um::winbase::CREATE_NO_WINDOW;
+
+use crate::ytdl::chapters::Chapter;
+use crate::ytdl::cookies::{cookie_args, CookieSource};
+use crate::ytdl::errors::DownloadError;
+use crate::ytdl::formats::{FormatInfo, YtdlpFormat};
+use crate::ytdl::network::network_args;
+use crate::ytdl::settings::load_settings;
+use crate::ytdl::subtitles::{subtitle_languages, AvailableSubtitles};
+
+/// What a URL points to, as classified from the yt-dlp extractor that handles it.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum VideoType {
+    /// A single video of any site without a more specific type
+    Video,
+    /// A YouTube clip, a section of another video
+    Clip,
+    /// A live or scheduled stream, recorded until it ends
+    Livestream,
+    /// Any list of videos (playlists, channels, sets, posts with several videos...)
+    Playlist,
+    TwitchVod,
+    TwitchClip,
+    XPost,
+    BilibiliVideo,
+    NiconicoVideo,
+    SoundCloudTrack,
+}
+
+impl VideoType {
+    /// Type of a single video handled by the given yt-dlp extractor.
+    pub(crate) fn from_extractor(extractor_key: &str) -> VideoType {
+        match extractor_key {
+            "YoutubeClip" => VideoType::Clip,
+            "TwitchVod" => VideoType::TwitchVod,
+            "TwitchClips" => VideoType::TwitchClip,
+            "Twitter" => VideoType::XPost,
+            "BiliBili" => VideoType::BilibiliVideo,
+            "Niconico" => VideoType::NiconicoVideo,
+            "Soundcloud" => VideoType::SoundCloudTrack,
+            _ => VideoType::Video,
+        }
+    }
+
+    /// Output template of the name of the downloaded files and of the unique folder.
+    pub(crate) fn name_template(&self) -> &'static str {
+        match self {
+            // The same stream title is often used for days
+            VideoType::TwitchVod => "%(upload_date>%Y-%m-%d)s %(title)s",
+            // Titles that don't tell videos apart (clips and posts named after their text)
+            VideoType::TwitchClip
+            | VideoType::XPost
+            | VideoType::BilibiliVideo
+            | VideoType::NiconicoVideo => "%(title)s [%(id)s]",
+            _ => "%(title)s",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LiveStatus {
+    NotLive,
+    IsLive,
+    IsUpcoming,
+    WasLive,
+    PostLive,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Who can watch the video.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Availability {
+    Public,
+    Unlisted,
+    Private,
+    PremiumOnly,
+    SubscriberOnly,
+    NeedsAuth,
+    #[serde(other)]
+    Unknown,
+}
+
+/// A video as reported by `yt-dlp -J`. Only the fields the app uses are kept.
+#[derive(Deserialize, Debug)]
+struct YtdlpVideo {
+    /// `video` (or missing) for single videos, `playlist` or `multi_video` for lists
+    #[serde(rename = "_type")]
+    result_type: Option<String>,
+    extractor_key: String,
+    id: String,
+    #[serde(default)]
+    title: String,
+    ext: Option<String>,
+    thumbnail: Option<String>,
+    uploader: Option<String>,
+    duration: Option<f64>,
+    /// `YYYYMMDD`
+    upload_date: Option<String>,
+    view_count: Option<u64>,
+    like_count: Option<u64>,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+    chapters: Option<Vec<Chapter>>,
+    formats: Option<Vec<YtdlpFormat>>,
+    subtitles: Option<HashMap<String, serde_json::Value>>,
+    automatic_captions: Option<HashMap<String, serde_json::Value>>,
+    live_status: Option<LiveStatus>,
+    availability: Option<Availability>,
+    /// Set when only a section of the video is downloaded, like YouTube clips
+    section_end: Option<f64>,
+}
+
+impl YtdlpVideo {
+    fn video_type(&self) -> VideoType {
+        if matches!(
+            self.result_type.as_deref(),
+            Some("playlist" | "multi_video")
+        ) {
+            return VideoType::Playlist;
+        }
+
+        if matches!(
+            self.live_status,
+            Some(LiveStatus::IsLive | LiveStatus::IsUpcoming)
+        ) {
+            return VideoType::Livestream;
+        }
+
+        // Clip URLs are handed over to the YouTube extractor, which cuts the clip's section
+        if self.extractor_key == "Youtube" && self.section_end.is_some() {
+            return VideoType::Clip;
+        }
+
+        VideoType::from_extractor(&self.extractor_key)
+    }
+}
+
+/// Details of a video, sent to the frontend before downloading it.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VideoInfo {
+    pub(crate) video_type: VideoType,
+    /// yt-dlp extractor that handles the URL (`Youtube`, `TwitchVod`, `Niconico`...)
+    pub(crate) extractor: String,
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) ext: String,
+    pub(crate) thumbnail: Option<String>,
+    pub(crate) uploader: Option<String>,
+    /// Seconds, `None` for livestreams
+    pub(crate) duration: Option<f64>,
+    pub(crate) upload_date: Option<NaiveDate>,
+    pub(crate) view_count: Option<u64>,
+    pub(crate) like_count: Option<u64>,
+    pub(crate) description: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) chapters: Vec<Chapter>,
+    pub(crate) formats: Vec<FormatInfo>,
+    pub(crate) subtitles: AvailableSubtitles,
+    pub(crate) live_status: Option<LiveStatus>,
+    pub(crate) availability: Option<Availability>,
+}
+
+impl VideoInfo {
+    /// Name of the video's unique folder, following its type's name template. Not sanitized.
+    pub(crate) fn folder_name(&self) -> String {
+        match (self.video_type, self.upload_date) {
+            (VideoType::TwitchVod, Some(date)) => {
+                format!("{} {}", date.format("%Y-%m-%d"), self.title)
+            }
+            (
+                VideoType::TwitchClip
+                | VideoType::XPost
+                | VideoType::BilibiliVideo
+                | VideoType::NiconicoVideo,
+                _,
+            ) => format!("{} [{}]", self.title, self.id),
+            _ => self.title.clone(),
+        }
+    }
+}
+
+impl From<YtdlpVideo> for VideoInfo {
+    fn from(video: YtdlpVideo) -> Self {
+        VideoInfo {
+            video_type: video.video_type(),
+            extractor: video.extractor_key,
+            id: video.id,
+            title: video.title,
+            ext: video.ext.unwrap_or_default(),
+            thumbnail: video.thumbnail,
+            uploader: video.uploader,
+            duration: video.duration,
+            upload_date: video
+                .upload_date
+                .and_then(|date| NaiveDate::parse_from_str(&date, "%Y%m%d").ok()),
+            view_count: video.view_count,
+            like_count: video.like_count,
+            description: video.description,
+            tags: video.tags.unwrap_or_default(),
+            chapters: video.chapters.unwrap_or_default(),
+            formats: video
+                .formats
+                .unwrap_or_default()
+                .into_iter()
+                .map(FormatInfo::from)
+                .collect(),
+            subtitles: AvailableSubtitles {
+                subtitles: subtitle_languages(video.subtitles),
+                automatic_captions: subtitle_languages(video.automatic_captions),
+            },
+            live_status: video.live_status,
+            availability: video.availability,
+        }
+    }
+}
+
+/// Fetches the details of a video with one `yt-dlp -J` call. Playlists are only listed, their
+/// entries are not extracted.
+pub(crate) fn get_video_info(
+    url: &str,
+    ytdlp_path: &str,
+    cookies: Option<&CookieSource>,
+) -> Result<VideoInfo, Box<dyn Error>> {
+    fetch_video_info(url, ytdlp_path, cookies).map(|(video_info, _)| video_info)
+}
+
+/// Same as `get_video_info`, also returning yt-dlp's info JSON so it can be passed back to
+/// yt-dlp with `--load-info-json`.
+pub(crate) fn fetch_video_info(
+    url: &str,
+    ytdlp_path: &str,
+    cookies: Option<&CookieSource>,
+) -> Result<(VideoInfo, Value), Box<dyn Error>> {
+    let output = Command::new(ytdlp_path)
+        .args(cookie_args(cookies)?)
+        .args(network_args(&load_settings().network))
+        .arg("--dump-single-json")
+        .arg("--no-playlist")
+        .arg("--flat-playlist")
+        // Streams that haven't started have no formats yet, they're still reported as upcoming
+        // so they can be waited for
+        .arg("--ignore-no-formats-error")
+        .arg(url)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(DownloadError::from_output(&String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    let info: Value = serde_json::from_slice(&output.stdout)?;
+    let video = YtdlpVideo::deserialize(&info)?;
+
+    Ok((VideoInfo::from(video), info))
+}
+
+/// Fetches the info JSON of one entry of a playlist, extracted through the playlist so its
+/// `playlist` and `playlist_index` fields are filled in.
+pub(crate) fn fetch_playlist_entry_info(
+    url: &str,
+    index: usize,
+    ytdlp_path: &str,
+    cookies: Option<&CookieSource>,
+) -> Result<Value, Box<dyn Error>> {
+    let output = Command::new(ytdlp_path)
+        .args(cookie_args(cookies)?)
+        .args(network_args(&load_settings().network))
+        .arg("--dump-single-json")
+        .arg("--playlist-items")
+        .arg(index.to_string())
+        .arg(url)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(DownloadError::from_output(&String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    let mut info: Value = serde_json::from_slice(&output.stdout)?;
+    info.get_mut("entries")
+        .and_then(Value::as_array_mut)
+        .filter(|entries| !entries.is_empty())
+        .map(|entries| entries.swap_remove(0))
+        .ok_or_else(|| format!("Playlist entry {} wasn't found", index).into())
+}
@@ -0,0 +1,175 @@
+use std::error::Error;
+use std::fs;
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Window};
+use winapi::um::winbase::CREATE_NO_WINDOW;
+
+use crate::ytdl::downloads::JobControl;
+use crate::ytdl::progress::{DownloadProgress, ProgressUpdate};
+
+/// How often yt-dlp checks again whether a scheduled stream started, unless the user changes it.
+const DEFAULT_WAIT_INTERVAL: u32 = 60;
+
+/// Options used when the URL is a livestream.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LiveOptions {
+    /// Record from the start of the stream instead of the current point
+    #[serde(default)]
+    pub(crate) from_start: bool,
+    /// Wait for a scheduled stream or premiere to begin instead of failing
+    #[serde(default)]
+    pub(crate) wait_for_start: bool,
+    /// Seconds between checks while waiting for the stream to begin
+    pub(crate) wait_interval: Option<u32>,
+}
+
+/// Builds the yt-dlp arguments for recording a livestream.
+pub(crate) fn live_args(live: &LiveOptions) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+
+    args.push(if live.from_start {
+        "--live-from-start".into()
+    } else {
+        "--no-live-from-start".into()
+    });
+
+    if live.wait_for_start {
+        args.push("--wait-for-video".into());
+        args.push(
+            live.wait_interval
+                .unwrap_or(DEFAULT_WAIT_INTERVAL)
+                .to_string(),
+        );
+    }
+
+    // MPEG-TS stays playable when the recording is cut, so a stopped recording can be remuxed
+    args.push("--hls-use-mpegts".into());
+
+    args
+}
+
+/// Emits the recorded time and size every second on `download_progress`, since a livestream
+/// has no percent. Returns a flag that stops the watcher once set.
+pub(crate) fn watch_recording(
+    job_id: u64,
+    control: Arc<JobControl>,
+    window: Window,
+) -> Arc<AtomicBool> {
+    let done = Arc::new(AtomicBool::new(false));
+    let done_clone = Arc::clone(&done);
+
+    tokio::spawn(async move {
+        let mut started: Option<Instant> = None;
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if done_clone.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let recorded_bytes = partial_files(&control.destinations())
+                .iter()
+                .filter_map(|file| fs::metadata(file).ok())
+                .map(|metadata| metadata.len())
+                .sum::<u64>();
+
+            // Still extracting or waiting for the stream to begin
+            if recorded_bytes == 0 && started.is_none() {
+                continue;
+            }
+            let started = *started.get_or_insert_with(Instant::now);
+
+            window
+                .emit(
+                    "download_progress",
+                    DownloadProgress {
+                        job_id,
+                        progress: ProgressUpdate::recording(
+                            started.elapsed().as_secs(),
+                            recorded_bytes,
+                        ),
+                    },
+                )
+                .unwrap();
+        }
+    });
+
+    done
+}
+
+/// Turns the `.part` files left by a stopped recording into the final file, copying the streams
+/// with ffmpeg (video and audio are separate files when recording from the start).
+pub(crate) fn finalize_recording(
+    control: &JobControl,
+    ffmpeg_path: &str,
+) -> Result<bool, Box<dyn Error>> {
+    let destinations = control.destinations();
+    let inputs = partial_files(&destinations);
+
+    // yt-dlp finished the recording itself
+    if inputs.is_empty() {
+        return Ok(true);
+    }
+
+    let output = recording_output_path(&destinations[0]);
+
+    let mut ffmpeg = Command::new(ffmpeg_path);
+    ffmpeg.arg("-y");
+    for input in &inputs {
+        ffmpeg.arg("-i").arg(input);
+    }
+    for index in 0..inputs.len() {
+        ffmpeg.arg("-map").arg(index.to_string());
+    }
+    let result = ffmpeg
+        .arg("-c")
+        .arg("copy")
+        .arg(&output)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+
+    if !result.status.success() {
+        return Err(format!(
+            "Failed to finalize the recording: {}",
+            String::from_utf8_lossy(&result.stderr)
+        )
+        .into());
+    }
+
+    for input in inputs {
+        let _ = fs::remove_file(input);
+    }
+
+    Ok(true)
+}
+
+/// The `.part` files of the given destinations that are still on disk.
+fn partial_files(destinations: &[PathBuf]) -> Vec<PathBuf> {
+    destinations
+        .iter()
+        .map(|destination| {
+            let mut part = destination.clone().into_os_string();
+            part.push(".part");
+            PathBuf::from(part)
+        })
+        .filter(|part| part.exists())
+        .collect()
+}
+
+/// Final name of a recording: the destination without the format ID yt-dlp adds to separate
+/// streams (`title.f299.mp4` becomes `title.mp4`).
+fn recording_output_path(destination: &Path) -> PathBuf {
+    let format_id = Regex::new(r"\.f[0-9A-Za-z_-]+(\.[^.]+)$").unwrap();
+    let destination = destination.to_string_lossy();
+
+    PathBuf::from(format_id.replace(&destination, "$1").into_owned())
+}
@@ -0,0 +1,186 @@
+use std::error::Error;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::ytdl::sanitize::{sanitize_filename, FilenameMode};
+use crate::ytdl::settings::config_dir;
+
+/// Which logs are kept, the oldest ones are deleted when a new download starts.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LogSettings {
+    #[serde(default = "default_max_count")]
+    pub(crate) max_count: usize,
+    /// Total size of the kept logs in bytes
+    #[serde(default = "default_max_bytes")]
+    pub(crate) max_bytes: u64,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            max_count: default_max_count(),
+            max_bytes: default_max_bytes(),
+        }
+    }
+}
+
+impl LogSettings {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.max_count == 0 {
+            return Err("At least one log must be kept".into());
+        }
+
+        if self.max_bytes == 0 {
+            return Err("The maximum size of the logs must be greater than 0".into());
+        }
+
+        Ok(())
+    }
+}
+
+fn default_max_count() -> usize {
+    50
+}
+
+fn default_max_bytes() -> u64 {
+    50 * 1024 * 1024
+}
+
+/// A download log, as listed for the log viewer.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LogFile {
+    /// File name, also used to read or export the log
+    pub(crate) name: String,
+    pub(crate) size: u64,
+    pub(crate) modified: DateTime<Utc>,
+}
+
+/// The `logs` folder in the app config directory.
+fn logs_dir() -> PathBuf {
+    config_dir().join("logs")
+}
+
+/// Creates the log of a download, named `<local time> <video ID>.log`, and deletes the oldest
+/// logs over the limits.
+pub(crate) fn create_job_log(
+    video_id: &str,
+    settings: &LogSettings,
+) -> Result<File, Box<dyn Error>> {
+    let dir = logs_dir();
+    fs::create_dir_all(&dir)?;
+
+    let name = format!(
+        "{} {}.log",
+        Local::now().format("%Y-%m-%d_%H-%M-%S"),
+        video_id
+    );
+    let path = dir.join(sanitize_filename(&name, FilenameMode::Ascii, 120));
+
+    // A download resumed within the same second keeps writing to the same log
+    let log = OpenOptions::new().create(true).append(true).open(&path)?;
+    prune_logs(settings, &path);
+
+    Ok(log)
+}
+
+/// Every log, newest first.
+pub(crate) fn list_logs() -> Vec<LogFile> {
+    let Ok(entries) = fs::read_dir(logs_dir()) else {
+        return Vec::new();
+    };
+
+    let mut logs: Vec<LogFile> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let metadata = entry.metadata().ok()?;
+            (metadata.is_file() && name.ends_with(".log")).then(|| LogFile {
+                name,
+                size: metadata.len(),
+                modified: metadata
+                    .modified()
+                    .map(DateTime::<Utc>::from)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+    logs.sort_by(|a, b| b.modified.cmp(&a.modified).then(b.name.cmp(&a.name)));
+
+    logs
+}
+
+pub(crate) fn read_log(name: &str) -> Result<String, Box<dyn Error>> {
+    let bytes = fs::read(log_path(name)?)?;
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Writes the given logs (every log when `names` is empty) to a zip file at `path`, to attach to
+/// bug reports. Returns how many logs were added.
+pub(crate) fn export_logs(path: &str, names: &[String]) -> Result<usize, Box<dyn Error>> {
+    let names: Vec<String> = if names.is_empty() {
+        list_logs().into_iter().map(|log| log.name).collect()
+    } else {
+        names.to_vec()
+    };
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for name in &names {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(&fs::read(log_path(name)?)?)?;
+    }
+
+    zip.finish()?;
+
+    Ok(names.len())
+}
+
+/// Path of a log from its name, refusing names that point outside of the logs folder.
+fn log_path(name: &str) -> Result<PathBuf, String> {
+    let is_file_name = Path::new(name)
+        .file_name()
+        .is_some_and(|file_name| file_name == name);
+    if !is_file_name || !name.ends_with(".log") {
+        return Err(format!("Invalid log name: {}", name));
+    }
+
+    let path = logs_dir().join(name);
+    if !path.is_file() {
+        return Err(format!("No log named {}", name));
+    }
+
+    Ok(path)
+}
+
+/// Deletes the oldest logs until at most `max_count` are left and they take at most `max_bytes`.
+/// The log of the download that is starting is always kept.
+fn prune_logs(settings: &LogSettings, current: &Path) {
+    let mut count = 0;
+    let mut size = 0;
+
+    for log in list_logs() {
+        let path = logs_dir().join(&log.name);
+        if path == current {
+            continue;
+        }
+
+        count += 1;
+        size += log.size;
+
+        // The current log counts as one
+        if count + 1 > settings.max_count || size > settings.max_bytes {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ytdl::templates::check_fields;
+
+/// Metadata fields `--add-metadata` writes into the file.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MetadataField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    Date,
+    Track,
+    Comment,
+    Description,
+    Synopsis,
+    /// The video's URL
+    Purl,
+}
+
+impl MetadataField {
+    fn name(&self) -> &'static str {
+        match self {
+            MetadataField::Title => "title",
+            MetadataField::Artist => "artist",
+            MetadataField::Album => "album",
+            MetadataField::AlbumArtist => "album_artist",
+            MetadataField::Genre => "genre",
+            MetadataField::Date => "date",
+            MetadataField::Track => "track",
+            MetadataField::Comment => "comment",
+            MetadataField::Description => "description",
+            MetadataField::Synopsis => "synopsis",
+            MetadataField::Purl => "purl",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MetadataOptions {
+    /// Write metadata into the file
+    #[serde(default = "default_embed")]
+    pub(crate) embed: bool,
+    /// yt-dlp template of each field, e.g. `artist` = `%(uploader)s` or `album` = `%(playlist)s`.
+    /// An empty template leaves the field out, fields left out get yt-dlp's defaults.
+    #[serde(default)]
+    pub(crate) fields: BTreeMap<MetadataField, String>,
+}
+
+impl Default for MetadataOptions {
+    fn default() -> Self {
+        MetadataOptions {
+            embed: default_embed(),
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+impl MetadataOptions {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for (field, template) in &self.fields {
+            check_fields(template)
+                .map_err(|e| format!("Invalid {} metadata template: {}", field.name(), e))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Metadata was always added, keep doing it unless asked not to
+fn default_embed() -> bool {
+    true
+}
+
+/// Builds the yt-dlp arguments that write the metadata, filling the chosen fields from their
+/// templates.
+pub(crate) fn metadata_args(metadata: &MetadataOptions) -> Vec<String> {
+    if !metadata.embed {
+        return Vec::new();
+    }
+
+    let mut args: Vec<String> = vec!["--add-metadata".into()];
+
+    // `meta_<field>` overrides what yt-dlp writes to <field>, an empty value removes it.
+    // FROM and TO are split on the first colon that isn't escaped.
+    for (field, template) in &metadata.fields {
+        args.push("--parse-metadata".into());
+        args.push(if template.is_empty() {
+            format!(":(?P<meta_{}>)", field.name())
+        } else {
+            format!("{}:%(meta_{})s", template.replace(':', "\\:"), field.name())
+        });
+    }
+
+    args
+}
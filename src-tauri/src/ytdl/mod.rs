@@ -1,4 +1,26 @@
+pub(crate) mod archive;
+pub(crate) mod chapters;
+pub(crate) mod cookies;
 pub(crate) mod downloads;
+pub(crate) mod errors;
+pub(crate) mod formats;
+pub(crate) mod history;
+pub(crate) mod info;
+pub(crate) mod live;
+pub(crate) mod logs;
+pub(crate) mod metadata;
+pub(crate) mod network;
+pub(crate) mod progress;
+pub(crate) mod queue;
+pub(crate) mod retry;
+pub(crate) mod sanitize;
+pub(crate) mod sections;
+pub(crate) mod settings;
+pub(crate) mod sponsorblock;
+pub(crate) mod subtitles;
+pub(crate) mod templates;
+pub(crate) mod thumbnails;
+pub(crate) mod url;
 
 #[macro_use]
 pub(crate) mod commands;
@@ -0,0 +1,170 @@
+use serde::Serialize;
+
+/// Marks the lines printed through `--progress-template`, so they can't be mistaken for regular output.
+const PROGRESS_PREFIX: &str = "[bl-progress]";
+
+/// Post-processors yt-dlp runs after the download, as they appear in its output (`[Metadata] ...`).
+const POST_PROCESSORS: [&str; 13] = [
+    "ExtractAudio",
+    "EmbedThumbnail",
+    "EmbedSubtitle",
+    "Metadata",
+    "FixupM3u8",
+    "FixupM4a",
+    "FixupStretched",
+    "FixupDuplicateMoov",
+    "FixupTimestamp",
+    "VideoRemuxer",
+    "VideoConvertor",
+    "ThumbnailsConvertor",
+    "MoveFiles",
+];
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum DownloadPhase {
+    Extracting,
+    DownloadingVideo,
+    DownloadingAudio,
+    Merging,
+    PostProcessing,
+    /// A livestream is being recorded, only `downloaded_bytes` and `elapsed` are known
+    Recording,
+}
+
+/// Typed progress of a single yt-dlp run. Fields yt-dlp doesn't know (yet) are `None`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProgressUpdate {
+    pub(crate) phase: DownloadPhase,
+    pub(crate) percent: Option<f64>,
+    pub(crate) downloaded_bytes: Option<u64>,
+    pub(crate) total_bytes: Option<u64>,
+    /// Bytes per second
+    pub(crate) speed: Option<f64>,
+    /// Seconds left
+    pub(crate) eta: Option<u64>,
+    /// Seconds since the download (or recording) started
+    pub(crate) elapsed: Option<u64>,
+}
+
+impl ProgressUpdate {
+    fn phase_only(phase: DownloadPhase) -> Self {
+        ProgressUpdate {
+            phase,
+            percent: None,
+            downloaded_bytes: None,
+            total_bytes: None,
+            speed: None,
+            eta: None,
+            elapsed: None,
+        }
+    }
+
+    pub(crate) fn recording(elapsed: u64, recorded_bytes: u64) -> Self {
+        ProgressUpdate {
+            downloaded_bytes: Some(recorded_bytes),
+            elapsed: Some(elapsed),
+            ..ProgressUpdate::phase_only(DownloadPhase::Recording)
+        }
+    }
+}
+
+/// Payload emitted on `download_progress`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadProgress {
+    pub(crate) job_id: u64,
+    #[serde(flatten)]
+    pub(crate) progress: ProgressUpdate,
+}
+
+/// Arguments that make yt-dlp print its progress in a format `ProgressParser` understands.
+pub(crate) fn progress_template_args() -> Vec<String> {
+    vec![
+        "--progress-template".into(),
+        format!(
+            "download:{} %(progress.status)s|%(progress.downloaded_bytes)s|%(progress.total_bytes)s|%(progress.total_bytes_estimate)s|%(progress.speed)s|%(progress.eta)s|%(progress.elapsed)s|%(info.vcodec)s|%(info.acodec)s",
+            PROGRESS_PREFIX
+        ),
+    ]
+}
+
+/// Turns the output lines of a single yt-dlp run into `ProgressUpdate`s.
+pub(crate) struct ProgressParser {
+    phase: DownloadPhase,
+}
+
+impl ProgressParser {
+    pub(crate) fn new() -> Self {
+        ProgressParser {
+            phase: DownloadPhase::Extracting,
+        }
+    }
+
+    /// Returns an update for progress lines and for lines that start a new phase, `None` otherwise.
+    pub(crate) fn parse_line(&mut self, line: &str) -> Option<ProgressUpdate> {
+        if let Some(progress) = line.strip_prefix(PROGRESS_PREFIX) {
+            return self.parse_progress(progress.trim());
+        }
+
+        let tag = line.strip_prefix('[')?.split(']').next()?;
+        let phase = if tag == "Merger" {
+            DownloadPhase::Merging
+        } else if POST_PROCESSORS.contains(&tag) {
+            DownloadPhase::PostProcessing
+        } else {
+            return None;
+        };
+
+        if phase == self.phase {
+            return None;
+        }
+        self.phase = phase;
+
+        Some(ProgressUpdate::phase_only(phase))
+    }
+
+    fn parse_progress(&mut self, progress: &str) -> Option<ProgressUpdate> {
+        let fields: Vec<&str> = progress.split('|').collect();
+        if fields.len() != 9 {
+            return None;
+        }
+
+        self.phase = match (fields[7], fields[8]) {
+            ("none", acodec) if acodec != "none" => DownloadPhase::DownloadingAudio,
+            _ => DownloadPhase::DownloadingVideo,
+        };
+
+        let downloaded_bytes = parse_number(fields[1]).map(|bytes| bytes as u64);
+        let total_bytes = parse_number(fields[2])
+            .or_else(|| parse_number(fields[3]))
+            .map(|bytes| bytes as u64);
+        let percent = match (downloaded_bytes, total_bytes) {
+            (Some(downloaded), Some(total)) if total > 0 => {
+                Some((downloaded as f64 / total as f64 * 100.0).min(100.0))
+            }
+            _ if fields[0] == "finished" => Some(100.0),
+            _ => None,
+        };
+
+        Some(ProgressUpdate {
+            phase: self.phase,
+            percent,
+            downloaded_bytes,
+            total_bytes,
+            speed: parse_number(fields[4]),
+            eta: parse_number(fields[5]).map(|eta| eta as u64),
+            elapsed: parse_number(fields[6]).map(|elapsed| elapsed as u64),
+        })
+    }
+}
+
+/// yt-dlp prints `NA` for fields it doesn't know.
+fn parse_number(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::async_runtime::spawn;
+use tauri::{Emitter, Window};
+
+use crate::ytdl::downloads::{
+    download_video, DownloadContext, DownloadOptions, JobControl, StopRequest,
+};
+use crate::ytdl::errors::{DownloadError, YtdlpError};
+use crate::ytdl::history::{add_record, DownloadOutcome, HistoryRecord};
+use crate::ytdl::info::VideoType;
+use crate::ytdl::retry::wait_before_retry;
+use crate::ytdl::settings::load_settings;
+
+/// How many downloads can run at the same time unless the user changes it.
+const DEFAULT_MAX_CONCURRENT: usize = 2;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// A single download in the queue.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadJob {
+    pub(crate) id: u64,
+    pub(crate) url: String,
+    pub(crate) options: DownloadOptions,
+    pub(crate) status: JobStatus,
+    pub(crate) error: Option<DownloadError>,
+}
+
+/// Payload emitted on `download_complete`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadComplete {
+    pub(crate) job_id: u64,
+}
+
+/// Payload emitted on `download_error`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadFailed {
+    pub(crate) job_id: u64,
+    pub(crate) error: DownloadError,
+}
+
+/// Payload emitted on `download_retrying` when a download failed for a transient reason and
+/// will be tried again.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadRetrying {
+    pub(crate) job_id: u64,
+    /// The attempt about to start, 2 for the first retry
+    pub(crate) attempt: u32,
+    pub(crate) max_attempts: u32,
+    /// Seconds until the attempt starts
+    pub(crate) delay: f64,
+    pub(crate) error: DownloadError,
+}
+
+/// Payload emitted on `history_error` when a job that ended couldn't be added to the history.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoryFailed {
+    pub(crate) job_id: u64,
+    pub(crate) error: String,
+}
+
+/// Payload emitted on `download_paused` and `download_cancelled`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DownloadStopped {
+    pub(crate) job_id: u64,
+}
+
+/// Ordered list of download jobs. Queued jobs are started front to back, as long as
+/// fewer than `max_concurrent` jobs are running.
+pub(crate) struct DownloadQueue {
+    jobs: Vec<DownloadJob>,
+    controls: HashMap<u64, Arc<JobControl>>,
+    max_concurrent: usize,
+    next_id: u64,
+}
+
+impl Default for DownloadQueue {
+    fn default() -> Self {
+        DownloadQueue {
+            jobs: Vec::new(),
+            controls: HashMap::new(),
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            next_id: 1,
+        }
+    }
+}
+
+impl DownloadQueue {
+    /// Adds a job at the end of the queue and returns its ID.
+    pub(crate) fn enqueue(&mut self, url: String, options: DownloadOptions) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.jobs.push(DownloadJob {
+            id,
+            url,
+            options,
+            status: JobStatus::Queued,
+            error: None,
+        });
+
+        id
+    }
+
+    pub(crate) fn jobs(&self) -> Vec<DownloadJob> {
+        self.jobs.clone()
+    }
+
+    pub(crate) fn set_max_concurrent(&mut self, limit: usize) -> Result<(), String> {
+        if limit == 0 {
+            return Err("The concurrency limit must be at least 1".to_string());
+        }
+        self.max_concurrent = limit;
+        Ok(())
+    }
+
+    pub(crate) fn set_status(&mut self, id: u64, status: JobStatus, error: Option<DownloadError>) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.status = status;
+            job.error = error;
+        }
+    }
+
+    /// Moves a job to `position` (clamped to the end of the queue).
+    pub(crate) fn move_job(&mut self, id: u64, position: usize) -> Result<(), String> {
+        let index = self.index_of(id)?;
+        let job = self.jobs.remove(index);
+        let position = position.min(self.jobs.len());
+        self.jobs.insert(position, job);
+        Ok(())
+    }
+
+    /// Cancels a queued job, or drops a job that is no longer active from the list.
+    pub(crate) fn remove(&mut self, id: u64) -> Result<(), String> {
+        let index = self.index_of(id)?;
+
+        match self.jobs[index].status {
+            JobStatus::Queued => self.jobs[index].status = JobStatus::Cancelled,
+            JobStatus::Running => {
+                return Err("The download is still running, cancel it first".to_string())
+            }
+            _ => {
+                self.controls.remove(&id);
+                self.jobs.remove(index);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stops a job. Running jobs get their yt-dlp process killed, queued jobs never start.
+    /// Returns the job and its control when a job that wasn't running was cancelled, so it can be
+    /// added to the history; running jobs are added when their download ends.
+    pub(crate) fn stop(
+        &mut self,
+        id: u64,
+        request: StopRequest,
+    ) -> Result<Option<(DownloadJob, Arc<JobControl>)>, String> {
+        let index = self.index_of(id)?;
+
+        match self.jobs[index].status {
+            JobStatus::Running => {
+                if let Some(control) = self.controls.get(&id) {
+                    // Stopping anything else than a recording would only leave partial files
+                    if request == StopRequest::StopRecording {
+                        match control.video_info() {
+                            Some(info) if info.video_type == VideoType::Livestream => {}
+                            Some(_) => return Err("The download is not a livestream".to_string()),
+                            None => return Err("The recording hasn't started".to_string()),
+                        }
+                    }
+
+                    control.stop(request);
+                }
+            }
+            JobStatus::Queued | JobStatus::Paused => {
+                // Paused jobs keep the files of their previous attempts until they're resumed
+                if let StopRequest::Cancel { delete_files } = request {
+                    let control = self.controls.remove(&id).unwrap_or_default();
+                    if delete_files {
+                        control.delete_partial_files();
+                    }
+
+                    self.jobs[index].status = JobStatus::Cancelled;
+                    return Ok(Some((self.jobs[index].clone(), control)));
+                }
+
+                self.jobs[index].status = match request {
+                    StopRequest::StopRecording => {
+                        return Err("The recording hasn't started".to_string())
+                    }
+                    _ => JobStatus::Paused,
+                };
+            }
+            _ => return Err("The download is not active".to_string()),
+        }
+
+        Ok(None)
+    }
+
+    /// Puts a paused job back in the queue. yt-dlp picks up from the `.part` files it left behind.
+    pub(crate) fn resume(&mut self, id: u64) -> Result<(), String> {
+        let index = self.index_of(id)?;
+
+        if self.jobs[index].status != JobStatus::Paused {
+            return Err("The download is not paused".to_string());
+        }
+        self.jobs[index].status = JobStatus::Queued;
+
+        Ok(())
+    }
+
+    /// Marks the next queued job as running and returns it, if a slot is free.
+    fn take_next(&mut self) -> Option<(DownloadJob, Arc<JobControl>)> {
+        let running = self
+            .jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Running)
+            .count();
+
+        if running >= self.max_concurrent {
+            return None;
+        }
+
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.status == JobStatus::Queued)?;
+        job.status = JobStatus::Running;
+        let job = job.clone();
+
+        // A resumed job still knows the files of its previous attempts, to delete them on cancel
+        let control = Arc::new(match self.controls.get(&job.id) {
+            Some(previous) => previous.resumed(),
+            None => JobControl::default(),
+        });
+        self.controls.insert(job.id, Arc::clone(&control));
+
+        Some((job, control))
+    }
+
+    /// Sets the final status of a job that stopped running and drops its control handle, unless
+    /// the job is paused.
+    fn finish(&mut self, id: u64, status: JobStatus, error: Option<DownloadError>) {
+        if status != JobStatus::Paused {
+            self.controls.remove(&id);
+        }
+        self.set_status(id, status, error);
+    }
+
+    fn index_of(&self, id: u64) -> Result<usize, String> {
+        self.jobs
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(|| format!("No download with ID {}", id))
+    }
+}
+
+/// Emits the current state of the queue on `download_queue_update`.
+pub(crate) fn emit_queue_update(queue: &Arc<Mutex<DownloadQueue>>, window: &Window) {
+    let jobs = queue.lock().unwrap().jobs();
+    window.emit("download_queue_update", jobs).unwrap();
+}
+
+/// Starts as many queued jobs as the concurrency limit allows. Each job starts the
+/// queue again when it ends, so the next one can take its slot.
+pub(crate) fn process_queue(
+    queue: Arc<Mutex<DownloadQueue>>,
+    ytdlp_path: String,
+    ffmpeg_path: String,
+    window: Window,
+) {
+    loop {
+        let (job, control) = match queue.lock().unwrap().take_next() {
+            Some(next) => next,
+            None => break,
+        };
+
+        emit_queue_update(&queue, &window);
+
+        let queue = Arc::clone(&queue);
+        let ytdlp_path = ytdlp_path.clone();
+        let ffmpeg_path = ffmpeg_path.clone();
+        let window = window.clone();
+
+        spawn(async move {
+            let started_at = Utc::now();
+
+            // The download runs in its own task, so the job still ends and frees its slot if it
+            // panics
+            let download = {
+                let (job, control, window) = (job.clone(), Arc::clone(&control), window.clone());
+                let (ytdlp_path, ffmpeg_path) = (ytdlp_path.clone(), ffmpeg_path.clone());
+
+                spawn(async move {
+                    let ctx = DownloadContext {
+                        job_id: job.id,
+                        ytdlp_path: &ytdlp_path,
+                        ffmpeg_path: &ffmpeg_path,
+                        window: &window,
+                        control: &control,
+                    };
+                    download_with_retries(&ctx, &job).await
+                })
+            };
+            let result = download.await.unwrap_or_else(|e| {
+                Err(DownloadError::new(
+                    YtdlpError::Unknown,
+                    format!("The download crashed: {}", e),
+                ))
+            });
+
+            match (control.stop_request(), result) {
+                (Some(StopRequest::Pause), _) => {
+                    queue
+                        .lock()
+                        .unwrap()
+                        .finish(job.id, JobStatus::Paused, None);
+                    window
+                        .emit("download_paused", DownloadStopped { job_id: job.id })
+                        .unwrap();
+                }
+                (Some(StopRequest::Cancel { delete_files }), _) => {
+                    if delete_files {
+                        control.delete_partial_files();
+                    }
+                    record_history(
+                        &window,
+                        &control,
+                        &job,
+                        started_at,
+                        DownloadOutcome::Cancelled,
+                        None,
+                    );
+                    queue
+                        .lock()
+                        .unwrap()
+                        .finish(job.id, JobStatus::Cancelled, None);
+                    window
+                        .emit("download_cancelled", DownloadStopped { job_id: job.id })
+                        .unwrap();
+                }
+                (_, Ok(_)) => {
+                    record_history(
+                        &window,
+                        &control,
+                        &job,
+                        started_at,
+                        DownloadOutcome::Finished,
+                        None,
+                    );
+                    queue
+                        .lock()
+                        .unwrap()
+                        .finish(job.id, JobStatus::Finished, None);
+                    window
+                        .emit("download_complete", DownloadComplete { job_id: job.id })
+                        .unwrap();
+                }
+                (_, Err(e)) => {
+                    record_history(
+                        &window,
+                        &control,
+                        &job,
+                        started_at,
+                        DownloadOutcome::Failed,
+                        Some(&e),
+                    );
+                    queue
+                        .lock()
+                        .unwrap()
+                        .finish(job.id, JobStatus::Failed, Some(e.clone()));
+                    window
+                        .emit(
+                            "download_error",
+                            DownloadFailed {
+                                job_id: job.id,
+                                error: e,
+                            },
+                        )
+                        .unwrap();
+                }
+            }
+
+            emit_queue_update(&queue, &window);
+            process_queue(queue, ytdlp_path, ffmpeg_path, window);
+        });
+    }
+}
+
+/// Downloads a job, trying again after transient failures. yt-dlp resumes from the partial files
+/// of the previous attempt.
+async fn download_with_retries(
+    ctx: &DownloadContext<'_>,
+    job: &DownloadJob,
+) -> Result<bool, DownloadError> {
+    let retry = load_settings().retry;
+    let mut attempt = 1;
+
+    loop {
+        let error = match download_video(ctx, &job.url, &job.options).await {
+            Ok(done) => return Ok(done),
+            Err(e) => DownloadError::from_boxed(e),
+        };
+
+        // A livestream recording can't pick up where it stopped, the stream moved on. Playlist
+        // entries are retried one by one.
+        let is_retried_elsewhere = ctx.control.video_info().is_some_and(|info| {
+            matches!(info.video_type, VideoType::Livestream | VideoType::Playlist)
+        });
+
+        if !retry.should_retry(&error, attempt)
+            || is_retried_elsewhere
+            || ctx.control.stop_request().is_some()
+        {
+            return Err(error);
+        }
+
+        let delay = retry.delay(attempt);
+        attempt += 1;
+        ctx.window
+            .emit(
+                "download_retrying",
+                DownloadRetrying {
+                    job_id: job.id,
+                    attempt,
+                    max_attempts: retry.max_attempts,
+                    delay: delay.as_secs_f64(),
+                    error: error.clone(),
+                },
+            )
+            .unwrap();
+
+        wait_before_retry(ctx.control, delay).await;
+
+        // Cancelled or paused while waiting
+        if ctx.control.stop_request().is_some() {
+            return Err(error);
+        }
+    }
+}
+
+/// Adds a job that was cancelled before it could run again to the download history.
+pub(crate) fn record_cancelled(window: &Window, job: &DownloadJob, control: &JobControl) {
+    record_history(
+        window,
+        control,
+        job,
+        Utc::now(),
+        DownloadOutcome::Cancelled,
+        None,
+    );
+}
+
+/// Adds a job that ended to the download history.
+fn record_history(
+    window: &Window,
+    control: &JobControl,
+    job: &DownloadJob,
+    started_at: DateTime<Utc>,
+    outcome: DownloadOutcome,
+    error: Option<&DownloadError>,
+) {
+    let video_info = control.video_info();
+    let files = control.files();
+    let size = files
+        .iter()
+        .filter_map(|file| fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+
+    let record = HistoryRecord {
+        id: 0,
+        url: job.url.clone(),
+        video_id: video_info.as_ref().map(|info| info.id.clone()),
+        title: video_info.as_ref().map(|info| info.title.clone()),
+        uploader: video_info.as_ref().and_then(|info| info.uploader.clone()),
+        options: job.options.clone(),
+        files,
+        size,
+        duration: video_info.as_ref().and_then(|info| info.duration),
+        started_at,
+        finished_at: Utc::now(),
+        outcome,
+        error: error.map(DownloadError::to_string),
+        error_code: error.map(|error| error.code),
+    };
+
+    if let Err(e) = add_record(record) {
+        window
+            .emit(
+                "history_error",
+                HistoryFailed {
+                    job_id: job.id,
+                    error: e.to_string(),
+                },
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::ytdl::downloads::JobControl;
+use crate::ytdl::errors::DownloadError;
+
+/// How often a download waiting to be retried checks whether it was cancelled or paused.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How downloads that failed for a transient reason (rate limiting, network errors...) are
+/// retried. yt-dlp resumes from the partial files on each attempt.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RetrySettings {
+    /// Attempts in total, 1 turns retrying off
+    #[serde(default = "default_max_attempts")]
+    pub(crate) max_attempts: u32,
+    /// Seconds before the first retry, doubled for every retry after it
+    #[serde(default = "default_base_delay")]
+    pub(crate) base_delay: u64,
+    /// Longest wait between two attempts, in seconds
+    #[serde(default = "default_max_delay")]
+    pub(crate) max_delay: u64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            max_attempts: default_max_attempts(),
+            base_delay: default_base_delay(),
+            max_delay: default_max_delay(),
+        }
+    }
+}
+
+impl RetrySettings {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("Downloads need at least one attempt".into());
+        }
+
+        if self.base_delay == 0 {
+            return Err("The retry delay must be greater than 0".into());
+        }
+
+        if self.max_delay < self.base_delay {
+            return Err("The longest retry delay can't be shorter than the first one".into());
+        }
+
+        Ok(())
+    }
+
+    /// Whether a download that failed with `error` on the given attempt (1 for the first one) is
+    /// tried again.
+    pub(crate) fn should_retry(&self, error: &DownloadError, attempt: u32) -> bool {
+        error.code.is_transient() && attempt < self.max_attempts
+    }
+
+    /// How long to wait before the given retry (1 for the first one). The delay doubles with
+    /// every retry, is spread by ±20% so downloads that failed together don't all retry at the
+    /// same time, and never exceeds `max_delay`.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << retry.saturating_sub(1).min(16));
+
+        Duration::from_secs(delay)
+            .mul_f64(rand::thread_rng().gen_range(0.8..1.2))
+            .min(Duration::from_secs(self.max_delay))
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_base_delay() -> u64 {
+    5
+}
+
+fn default_max_delay() -> u64 {
+    300
+}
+
+/// Waits before retrying a download, returning early if it's cancelled or paused meanwhile.
+pub(crate) async fn wait_before_retry(control: &JobControl, delay: Duration) {
+    let deadline = Instant::now() + delay;
+
+    while control.stop_request().is_none() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+
+        tokio::time::sleep(left.min(STOP_POLL_INTERVAL)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_transient_errors_until_the_last_attempt() {
+        let retry = RetrySettings::default();
+        let error = DownloadError::from_output("ERROR: fragment 7 not found, unable to continue");
+
+        assert!(retry.should_retry(&error, 1));
+        assert!(retry.should_retry(&error, 2));
+        assert!(!retry.should_retry(&error, 3));
+    }
+
+    #[test]
+    fn doesnt_retry_permanent_errors() {
+        let retry = RetrySettings::default();
+        let error = DownloadError::from_output(
+            "ERROR: [youtube] dQw4w9WgXcQ: Video unavailable. This video has been removed by the uploader",
+        );
+
+        assert!(!retry.should_retry(&error, 1));
+    }
+
+    #[test]
+    fn delays_double_with_jitter() {
+        let retry = RetrySettings::default();
+
+        for _ in 0..100 {
+            let first = retry.delay(1);
+            assert!(first >= Duration::from_secs(4) && first <= Duration::from_secs(6));
+
+            let third = retry.delay(3);
+            assert!(third >= Duration::from_secs(16) && third <= Duration::from_secs(24));
+        }
+    }
+
+    #[test]
+    fn delays_never_exceed_the_maximum() {
+        let retry = RetrySettings::default();
+
+        for attempt in 1..40 {
+            assert!(retry.delay(attempt) <= Duration::from_secs(retry.max_delay));
+        }
+
+        // The jitter can't push the capped delay over the maximum either
+        let retry = RetrySettings {
+            max_delay: 5,
+            ..RetrySettings::default()
+        };
+        for _ in 0..100 {
+            assert!(retry.delay(1) <= Duration::from_secs(5));
+        }
+    }
+}
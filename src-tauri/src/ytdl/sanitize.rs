@@ -0,0 +1,416 @@
+use deunicode::deunicode_with_tofu;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Device names Windows reserves, with or without an extension (`con.mp4` can't be created either).
+const RESERVED_NAMES: [&str; 28] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "COM¹", "COM²", "COM³", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+    "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// Name used when nothing is left of a title once it's sanitized.
+const EMPTY_NAME: &str = "untitled";
+
+/// How titles are turned into file and folder names.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FilenameMode {
+    /// Keeps the title's characters, replacing the ones Windows forbids with look-alikes
+    /// (`：`, `？`, `／`...), like yt-dlp does
+    #[default]
+    Unicode,
+    /// Transliterates the title to ASCII (`Café` becomes `Cafe`, `東京` becomes `Dong Jing`) and
+    /// drops emoji
+    Ascii,
+}
+
+/// File and folder naming, saved with the app settings.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FilenameSettings {
+    #[serde(default)]
+    pub(crate) mode: FilenameMode,
+    /// Maximum length of a file or folder name in bytes, extension included
+    #[serde(default = "default_max_bytes")]
+    pub(crate) max_bytes: usize,
+}
+
+impl Default for FilenameSettings {
+    fn default() -> Self {
+        FilenameSettings {
+            mode: FilenameMode::default(),
+            max_bytes: default_max_bytes(),
+        }
+    }
+}
+
+impl FilenameSettings {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if !(32..=250).contains(&self.max_bytes) {
+            return Err("The maximum file name length must be between 32 and 250 bytes".into());
+        }
+
+        Ok(())
+    }
+}
+
+// Leaves room for the download folder within Windows' 260 character path limit
+fn default_max_bytes() -> usize {
+    150
+}
+
+/// Builds the yt-dlp arguments that name the files it writes the same way.
+pub(crate) fn filename_args(filenames: &FilenameSettings) -> Vec<String> {
+    let mut args: Vec<String> = vec!["--trim-filenames".into(), filenames.max_bytes.to_string()];
+
+    if filenames.mode == FilenameMode::Ascii {
+        args.push("--restrict-filenames".into());
+    }
+
+    args
+}
+
+/// Turns a title into a folder name that is valid on Windows and at most `max_bytes` long.
+pub(crate) fn sanitize_folder_name(name: &str, mode: FilenameMode, max_bytes: usize) -> String {
+    sanitize(name, None, mode, max_bytes)
+}
+
+/// Turns a file name into one that is valid on Windows and at most `max_bytes` long. The
+/// extension is kept when the name has to be truncated.
+pub(crate) fn sanitize_filename(name: &str, mode: FilenameMode, max_bytes: usize) -> String {
+    // Only short alphanumeric suffixes are extensions, "Vol. 2.5 Live" has none
+    match name.rsplit_once('.') {
+        Some((stem, ext))
+            if !stem.trim().is_empty()
+                && (1..=5).contains(&ext.len())
+                && ext.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            sanitize(stem, Some(ext), mode, max_bytes)
+        }
+        _ => sanitize(name, None, mode, max_bytes),
+    }
+}
+
+fn sanitize(stem: &str, ext: Option<&str>, mode: FilenameMode, max_bytes: usize) -> String {
+    let stem = match mode {
+        FilenameMode::Unicode => stem.to_string(),
+        FilenameMode::Ascii => to_ascii(stem),
+    };
+
+    let mut name = String::new();
+    for c in stem.chars() {
+        match replace_char(c, mode) {
+            Some(replacement) => name.push_str(replacement),
+            None if is_invisible(c) => {}
+            None => name.push(c),
+        }
+    }
+
+    // Runs of spaces (and the control characters turned into spaces) are collapsed
+    let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    let ext = ext.map(|ext| format!(".{}", ext.to_ascii_lowercase()));
+    let ext_len = ext.as_ref().map_or(0, String::len);
+    // The reserved name suffix may be added after truncating
+    let mut name = truncate(&name, max_bytes.saturating_sub(ext_len + 1));
+
+    // Windows drops trailing dots and spaces, which makes the name point to another file
+    let trimmed_len = name.trim_end_matches(['.', ' ']).len();
+    name.truncate(trimmed_len);
+
+    if name.is_empty() {
+        name = EMPTY_NAME.into();
+    }
+
+    let device = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(device))
+    {
+        name.insert(device.len(), '_');
+    }
+
+    name + ext.as_deref().unwrap_or_default()
+}
+
+/// Transliterates to ASCII, dropping emoji instead of spelling out their names.
+fn to_ascii(text: &str) -> String {
+    text.graphemes(true)
+        .filter(|grapheme| !grapheme.chars().any(is_emoji))
+        .map(|grapheme| deunicode_with_tofu(grapheme, ""))
+        .collect()
+}
+
+/// The replacement of a character Windows doesn't allow in names, `None` to keep it.
+fn replace_char(c: char, mode: FilenameMode) -> Option<&'static str> {
+    let replacement = match (c, mode) {
+        (c, _) if c.is_control() => " ",
+        ('/', FilenameMode::Unicode) => "／",
+        ('\\', FilenameMode::Unicode) => "＼",
+        (':', FilenameMode::Unicode) => "：",
+        ('*', FilenameMode::Unicode) => "＊",
+        ('?', FilenameMode::Unicode) => "？",
+        ('"', FilenameMode::Unicode) => "＂",
+        ('<', FilenameMode::Unicode) => "＜",
+        ('>', FilenameMode::Unicode) => "＞",
+        ('|', FilenameMode::Unicode) => "｜",
+        ('/' | '\\' | ':' | '|', FilenameMode::Ascii) => "-",
+        ('"', FilenameMode::Ascii) => "'",
+        ('<', FilenameMode::Ascii) => "(",
+        ('>', FilenameMode::Ascii) => ")",
+        ('*' | '?', FilenameMode::Ascii) => "",
+        _ => return None,
+    };
+
+    Some(replacement)
+}
+
+/// Bidirectional overrides and other invisible formatting characters, which can make a name
+/// display differently from what it is. The zero width joiner is kept for emoji sequences.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}' | '\u{200C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}' | '\u{2066}'..='\u{2069}' | '\u{FEFF}'
+    )
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F000}'..='\u{1FAFF}' | '\u{2600}'..='\u{27BF}' | '\u{FE0F}' | '\u{200D}' | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+/// Cuts `name` to at most `max_bytes` bytes without splitting a character or an emoji sequence.
+fn truncate(name: &str, max_bytes: usize) -> String {
+    let mut truncated = String::new();
+
+    for grapheme in name.graphemes(true) {
+        if truncated.len() + grapheme.len() > max_bytes {
+            break;
+        }
+        truncated.push_str(grapheme);
+    }
+
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unicode(name: &str) -> String {
+        sanitize_filename(name, FilenameMode::Unicode, 150)
+    }
+
+    fn ascii(name: &str) -> String {
+        sanitize_filename(name, FilenameMode::Ascii, 150)
+    }
+
+    #[test]
+    fn replaces_forbidden_characters() {
+        assert_eq!(
+            unicode("AC/DC - Back In Black (Official Video).mp4"),
+            "AC／DC - Back In Black (Official Video).mp4"
+        );
+        assert_eq!(
+            unicode("Q&A: What is <this>? | \"Live\" *NEW*"),
+            "Q&A： What is ＜this＞？ ｜ ＂Live＂ ＊NEW＊"
+        );
+        assert_eq!(
+            ascii("Q&A: What is <this>? | \"Live\" *NEW*"),
+            "Q&A- What is (this) - 'Live' NEW"
+        );
+        assert_eq!(ascii("C:\\Windows\\System32"), "C--Windows-System32");
+    }
+
+    #[test]
+    fn never_creates_folders() {
+        for title in ["../../etc/passwd", "a/b\\c", "Part 1/2", "//", ".."] {
+            for name in [unicode(title), ascii(title)] {
+                assert!(!name.contains(['/', '\\']), "{}", name);
+                assert_ne!(name, "..");
+                assert_ne!(name, ".");
+            }
+        }
+        assert_eq!(unicode(".."), EMPTY_NAME);
+    }
+
+    #[test]
+    fn trims_trailing_dots_and_spaces() {
+        assert_eq!(unicode("To be continued..."), "To be continued");
+        assert_eq!(unicode("Wait for it . . ."), "Wait for it");
+        assert_eq!(unicode("  spaced   out  title  "), "spaced out title");
+        assert_eq!(unicode("Ends with dot..mp4"), "Ends with dot.mp4");
+    }
+
+    #[test]
+    fn removes_control_and_invisible_characters() {
+        assert_eq!(
+            unicode("Line one\nLine two\tTabbed"),
+            "Line one Line two Tabbed"
+        );
+        assert_eq!(unicode("\u{202E}4pm.exe"), "4pm.exe");
+        assert_eq!(unicode("zero\u{200B}width"), "zerowidth");
+        assert_eq!(unicode("\u{FEFF}BOM title"), "BOM title");
+    }
+
+    #[test]
+    fn keeps_unicode_titles() {
+        assert_eq!(
+            unicode("【MV】夜に駆ける / YOASOBI"),
+            "【MV】夜に駆ける ／ YOASOBI"
+        );
+        assert_eq!(unicode("Café Ünïcödé"), "Café Ünïcödé");
+        assert_eq!(unicode("👨‍👩‍👧 family vlog 🎉🎉"), "👨‍👩‍👧 family vlog 🎉🎉");
+        assert_eq!(unicode("🇯🇵 Japan trip"), "🇯🇵 Japan trip");
+    }
+
+    #[test]
+    fn transliterates_to_ascii() {
+        assert_eq!(ascii("Café Ünïcödé"), "Cafe Unicode");
+        assert_eq!(ascii("Привет мир"), "Privet mir");
+        assert_eq!(ascii("👨‍👩‍👧 family vlog 🎉🎉"), "family vlog");
+        assert_eq!(ascii("🇯🇵 Japan trip ❤️"), "Japan trip");
+        assert_eq!(ascii("½ price"), "1-2 price");
+        assert_eq!(ascii("🔥🔥🔥"), EMPTY_NAME);
+
+        for title in ["【MV】夜に駆ける / YOASOBI", "ß æ œ", "Ελληνικά"] {
+            assert!(ascii(title).is_ascii(), "{}", ascii(title));
+        }
+    }
+
+    #[test]
+    fn avoids_reserved_names() {
+        assert_eq!(unicode("CON"), "CON_");
+        assert_eq!(unicode("con.mp4"), "con_.mp4");
+        assert_eq!(unicode("Nul"), "Nul_");
+        assert_eq!(unicode("COM1.txt"), "COM1_.txt");
+        assert_eq!(unicode("LPT9 "), "LPT9_");
+        assert_eq!(unicode("aux.tar.gz"), "aux_.tar.gz");
+        assert_eq!(unicode("CONSOLE.mp4"), "CONSOLE.mp4");
+        assert_eq!(unicode("COM10"), "COM10");
+    }
+
+    #[test]
+    fn empty_titles_get_a_name() {
+        assert_eq!(unicode(""), EMPTY_NAME);
+        assert_eq!(unicode("   "), EMPTY_NAME);
+        assert_eq!(unicode("..."), EMPTY_NAME);
+        assert_eq!(unicode(" . "), EMPTY_NAME);
+        assert_eq!(unicode("\u{202E}\u{200B}"), EMPTY_NAME);
+    }
+
+    #[test]
+    fn truncates_by_bytes_keeping_the_extension() {
+        let long = "a".repeat(300);
+        let name = sanitize_filename(&format!("{}.webm", long), FilenameMode::Unicode, 100);
+        assert!(name.len() <= 100);
+        assert!(name.ends_with("aaaa.webm"));
+
+        // Three bytes per character
+        let long = "夜".repeat(100);
+        let name = sanitize_filename(&format!("{}.mp4", long), FilenameMode::Unicode, 100);
+        assert!(name.len() <= 100);
+        assert!(name.ends_with("夜.mp4"));
+
+        let folder = sanitize_folder_name(&long, FilenameMode::Unicode, 100);
+        assert!(folder.len() <= 100);
+        assert!(folder.chars().all(|c| c == '夜'));
+    }
+
+    #[test]
+    fn truncation_keeps_emoji_sequences_whole() {
+        // Family emoji: 18 bytes joined by zero width joiners
+        let family = "👨‍👩‍👧";
+        let title = format!("{}{}", "a".repeat(40), family.repeat(5));
+
+        for max_bytes in 40..80 {
+            let name = sanitize_folder_name(&title, FilenameMode::Unicode, max_bytes);
+            assert!(name.len() <= max_bytes);
+            assert!(!name.ends_with('\u{200D}'), "{:?}", name);
+            assert_eq!(name.trim_start_matches('a').len() % family.len(), 0);
+        }
+    }
+
+    #[test]
+    fn truncation_doesnt_leave_trailing_dots_or_reserved_names() {
+        let name = sanitize_folder_name("Chapter 1. The beginning", FilenameMode::Unicode, 10);
+        assert_eq!(name, "Chapter 1");
+
+        let name = sanitize_folder_name("CON. is a reserved name", FilenameMode::Unicode, 4);
+        assert_eq!(name, "CON_");
+    }
+
+    /// Titles seen in the wild, every name made from them has to be valid on Windows.
+    const CORPUS: [&str; 16] = [
+        "Rick Astley - Never Gonna Give You Up (Official Music Video)",
+        "【MV】YOASOBI「アイドル」/ Idol Official Music Video",
+        "What's inside? | Can we fix it?? (Part 1/3)",
+        "C:\\Program Files\\ is NOT a good place... <3",
+        "\"Quoted\" title with *stars* & <brackets>",
+        "🔥🔥 BEST OF 2023 🔥🔥 | 👨‍👩‍👧‍👦 Family edition 🇺🇸🇬🇧",
+        "Lo-fi hip hop radio 📚 - beats to relax/study to",
+        "Ｆｕｌｌｗｉｄｔｈ ｔｉｔｌｅ",
+        "العربية والعبرية עברית mixed direction",
+        "Zalgo t̷̢̛e̵̡͝x̴̧̛t̶̨̛",
+        "con",
+        "LPT1.mp4",
+        "   leading and trailing spaces   ",
+        "...",
+        "Title ending with ellipsis…",
+        "Tab\there\nand newline\r\n",
+    ];
+
+    #[test]
+    fn corpus_names_are_valid_on_windows() {
+        let mut titles: Vec<String> = CORPUS.iter().map(|title| title.to_string()).collect();
+        titles.push("Very long title ".repeat(40));
+        titles.push("とても長いタイトル".repeat(30));
+
+        for title in &titles {
+            for mode in [FilenameMode::Unicode, FilenameMode::Ascii] {
+                for max_bytes in [32, 64, 150, 250] {
+                    let names = [
+                        sanitize_folder_name(title, mode, max_bytes),
+                        sanitize_filename(&format!("{}.mp4", title), mode, max_bytes),
+                    ];
+
+                    for name in names {
+                        assert!(!name.is_empty());
+                        assert!(name.len() <= max_bytes, "{:?}", name);
+                        assert!(
+                            !name.contains(|c: char| "<>:\"/\\|?*".contains(c) || c.is_control()),
+                            "{:?}",
+                            name
+                        );
+                        assert!(!name.ends_with(['.', ' ']), "{:?}", name);
+                        assert!(!name.starts_with(' '), "{:?}", name);
+
+                        let device = name.split('.').next().unwrap();
+                        assert!(
+                            !RESERVED_NAMES
+                                .iter()
+                                .any(|reserved| reserved.eq_ignore_ascii_case(device.trim_end())),
+                            "{:?}",
+                            name
+                        );
+
+                        if mode == FilenameMode::Ascii {
+                            assert!(name.is_ascii(), "{:?}", name);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn detects_extensions() {
+        assert_eq!(unicode("Vol. 2.5 Live"), "Vol. 2.5 Live");
+        assert_eq!(unicode("Episode 3.MKV"), "Episode 3.mkv");
+        assert_eq!(unicode("Version 1.0.0"), "Version 1.0.0");
+    }
+}
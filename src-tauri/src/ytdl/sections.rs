@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+/// Output file name used when only sections are downloaded, so clips of the same video don't
+/// overwrite each other (`title [00-02-00 - 00-04-00].mp4`).
+pub(crate) const SECTION_FILE_TEMPLATE: &str =
+    "%(title)s [%(section_start>%H-%M-%S)s - %(section_end>%H-%M-%S)s].%(ext)s";
+
+/// A part of a video, as timestamps (`SS`, `MM:SS` or `HH:MM:SS`, with optional fractions).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TimeRange {
+    pub(crate) start: String,
+    pub(crate) end: String,
+}
+
+/// Which parts of a video to download. No ranges means the whole video.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SectionOptions {
+    #[serde(default)]
+    pub(crate) ranges: Vec<TimeRange>,
+    /// Cut exactly at the timestamps by re-encoding around them. Otherwise the streams are only
+    /// copied, which is fast but cuts at the nearest keyframe.
+    #[serde(default)]
+    pub(crate) precise_cuts: bool,
+}
+
+impl SectionOptions {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+/// Builds the yt-dlp arguments that download only the chosen sections, one file per section.
+pub(crate) fn section_args(sections: &SectionOptions) -> Result<Vec<String>, String> {
+    let mut args: Vec<String> = Vec::new();
+
+    for range in &sections.ranges {
+        let start = parse_timestamp(&range.start)?;
+        let end = parse_timestamp(&range.end)?;
+
+        if end <= start {
+            return Err(format!(
+                "The section {} - {} ends before it starts",
+                range.start, range.end
+            ));
+        }
+
+        args.push("--download-sections".into());
+        args.push(format!("*{}-{}", start, end));
+    }
+
+    if sections.precise_cuts && !args.is_empty() {
+        args.push("--force-keyframes-at-cuts".into());
+    }
+
+    Ok(args)
+}
+
+/// Parses `SS`, `MM:SS` or `HH:MM:SS` into seconds.
+fn parse_timestamp(timestamp: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid timestamp: {}", timestamp);
+
+    let parts: Vec<&str> = timestamp.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0.0;
+    for part in parts {
+        let value: f64 = part.parse().map_err(|_| invalid())?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+
+    Ok(seconds)
+}
@@ -0,0 +1,72 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ytdl::logs::LogSettings;
+use crate::ytdl::network::NetworkSettings;
+use crate::ytdl::retry::RetrySettings;
+use crate::ytdl::sanitize::FilenameSettings;
+use crate::ytdl::sponsorblock::SponsorBlockSettings;
+use crate::ytdl::templates::TemplateSettings;
+
+/// Defaults kept between sessions, saved as `settings.json` in the app config directory.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Settings {
+    #[serde(default)]
+    pub(crate) sponsorblock: SponsorBlockSettings,
+    #[serde(default)]
+    pub(crate) network: NetworkSettings,
+    /// Output template presets
+    #[serde(default)]
+    pub(crate) templates: TemplateSettings,
+    /// How titles are turned into file and folder names
+    #[serde(default)]
+    pub(crate) filenames: FilenameSettings,
+    /// How many download logs are kept
+    #[serde(default)]
+    pub(crate) logs: LogSettings,
+    /// How downloads that failed for a transient reason are retried
+    #[serde(default)]
+    pub(crate) retry: RetrySettings,
+}
+
+impl Settings {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        self.sponsorblock.validate()?;
+        self.network.validate()?;
+        self.templates.validate()?;
+        self.filenames.validate()?;
+        self.logs.validate()?;
+        self.retry.validate()
+    }
+}
+
+/// The app's folder in the user config directory, where its settings and data are kept.
+pub(crate) fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .expect("Failed to get config directory")
+        .join("Blue Lady's Tools")
+}
+
+fn settings_path() -> PathBuf {
+    config_dir().join("settings.json")
+}
+
+/// Reads the saved settings. Missing or unreadable settings give the defaults.
+pub(crate) fn load_settings() -> Settings {
+    fs::read_to_string(settings_path())
+        .ok()
+        .and_then(|settings| serde_json::from_str(&settings).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn save_settings(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let path = settings_path();
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, serde_json::to_string_pretty(settings)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// SponsorBlock segment categories the app supports.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SponsorBlockCategory {
+    Sponsor,
+    Intro,
+    Outro,
+    Selfpromo,
+    MusicOfftopic,
+}
+
+impl SponsorBlockCategory {
+    fn name(&self) -> &'static str {
+        match self {
+            SponsorBlockCategory::Sponsor => "sponsor",
+            SponsorBlockCategory::Intro => "intro",
+            SponsorBlockCategory::Outro => "outro",
+            SponsorBlockCategory::Selfpromo => "selfpromo",
+            SponsorBlockCategory::MusicOfftopic => "music_offtopic",
+        }
+    }
+}
+
+/// What to do with the segments of a category.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SegmentAction {
+    /// Add the segments as chapters
+    Mark,
+    /// Cut the segments out of the video
+    Remove,
+}
+
+/// Action per category. Categories left out are ignored.
+pub(crate) type SponsorBlockOptions = BTreeMap<SponsorBlockCategory, SegmentAction>;
+
+/// SponsorBlock defaults, saved with the app settings.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SponsorBlockSettings {
+    /// Used by downloads that don't choose their own actions
+    #[serde(default)]
+    pub(crate) categories: SponsorBlockOptions,
+    /// SponsorBlock API to query instead of the public one (e.g. a local server for testing)
+    pub(crate) api_url: Option<String>,
+}
+
+impl SponsorBlockSettings {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match &self.api_url {
+            Some(url) if !(url.starts_with("http://") || url.starts_with("https://")) => {
+                Err(format!(
+                    "The SponsorBlock API URL must start with http:// or https://: {}",
+                    url
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Builds the yt-dlp arguments that mark or remove the SponsorBlock segments of a video.
+pub(crate) fn sponsorblock_args(
+    categories: &SponsorBlockOptions,
+    api_url: Option<&str>,
+) -> Vec<String> {
+    let names = |action: SegmentAction| {
+        categories
+            .iter()
+            .filter(|(_, category_action)| **category_action == action)
+            .map(|(category, _)| category.name())
+            .collect::<Vec<&str>>()
+            .join(",")
+    };
+    let (mark, remove) = (names(SegmentAction::Mark), names(SegmentAction::Remove));
+
+    let mut args: Vec<String> = Vec::new();
+
+    if !mark.is_empty() {
+        args.push("--sponsorblock-mark".into());
+        args.push(mark);
+    }
+
+    if !remove.is_empty() {
+        args.push("--sponsorblock-remove".into());
+        args.push(remove);
+    }
+
+    // Only queried when some category is used
+    if args.is_empty() {
+        return args;
+    }
+
+    if let Some(api_url) = api_url {
+        args.push("--sponsorblock-api".into());
+        args.push(api_url.trim_end_matches('/').into());
+    }
+
+    args
+}
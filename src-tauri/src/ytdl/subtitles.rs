@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Subtitle languages a video has, as yt-dlp language codes (`en`, `pt-BR`, ...).
+#[derive(Serialize, Clone, PartialEq, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AvailableSubtitles {
+    /// Subtitles uploaded with the video
+    pub(crate) subtitles: Vec<String>,
+    /// Captions generated by the site (YouTube's auto-captions and their translations)
+    pub(crate) automatic_captions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SubtitleFormat {
+    #[default]
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+            SubtitleFormat::Ass => "ass",
+        }
+    }
+}
+
+/// Which subtitles to download and how to save them. No languages means no subtitles.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SubtitleOptions {
+    #[serde(default)]
+    pub(crate) languages: Vec<String>,
+    /// Also use auto-captions for languages without uploaded subtitles
+    #[serde(default)]
+    pub(crate) auto_captions: bool,
+    #[serde(default)]
+    pub(crate) format: SubtitleFormat,
+    /// Embed the subtitles into the video instead of writing them next to it
+    #[serde(default)]
+    pub(crate) embed: bool,
+}
+
+/// Builds the yt-dlp arguments that download the chosen subtitles.
+pub(crate) fn subtitle_args(subtitles: &SubtitleOptions) -> Vec<String> {
+    if subtitles.languages.is_empty() {
+        return Vec::new();
+    }
+
+    let mut args: Vec<String> = vec!["--write-subs".into()];
+
+    // --embed-subs only writes the uploaded subtitles itself when no other subtitles are written,
+    // so they're always written. Together with --write-subs it keeps the files next to the video,
+    // unless told not to.
+    if subtitles.embed {
+        args.push("--embed-subs".into());
+        args.push("--compat-options".into());
+        args.push("no-keep-subs".into());
+    }
+
+    if subtitles.auto_captions {
+        args.push("--write-auto-subs".into());
+    }
+
+    args.push("--sub-langs".into());
+    args.push(subtitles.languages.join(","));
+
+    let format = subtitles.format.name();
+    args.push("--sub-format".into());
+    args.push(format!("{}/best", format));
+    args.push("--convert-subs".into());
+    args.push(format.into());
+
+    args
+}
+
+/// The languages of yt-dlp's `subtitles` or `automatic_captions` field, sorted.
+pub(crate) fn subtitle_languages(
+    subtitles: Option<HashMap<String, serde_json::Value>>,
+) -> Vec<String> {
+    let Some(subtitles) = subtitles else {
+        return Vec::new();
+    };
+
+    let mut languages: Vec<String> = subtitles.into_keys().collect();
+    languages.sort();
+    languages
+}
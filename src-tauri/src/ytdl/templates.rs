@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+/// Characters Windows doesn't allow in file and folder names. Inside `%(...)s` fields they are
+/// part of yt-dlp's syntax (`>` for date formats, `|` for defaults...) and are allowed there.
+const FORBIDDEN_CHARACTERS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+/// Conversions yt-dlp accepts after a field, e.g. `s` in `%(title)s` or `d` in `%(playlist_index)03d`.
+const CONVERSIONS: &str = "diouxXeEfFgGcrsaBjhlqDSU";
+
+/// A named output template the user can pick for a download.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TemplatePreset {
+    pub(crate) name: String,
+    /// yt-dlp output template relative to the download folder, `/` separates folders
+    pub(crate) template: String,
+}
+
+impl TemplatePreset {
+    fn new(name: &str, template: &str) -> TemplatePreset {
+        TemplatePreset {
+            name: name.into(),
+            template: template.into(),
+        }
+    }
+}
+
+/// Output template presets, saved with the app settings.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TemplateSettings {
+    #[serde(default)]
+    pub(crate) presets: Vec<TemplatePreset>,
+}
+
+impl Default for TemplateSettings {
+    fn default() -> Self {
+        TemplateSettings {
+            presets: vec![
+                TemplatePreset::new("Title", "%(title)s.%(ext)s"),
+                TemplatePreset::new(
+                    "Archive",
+                    "%(uploader)s/%(upload_date>%Y-%m-%d)s - %(title)s [%(id)s].%(ext)s",
+                ),
+                TemplatePreset::new("Playlist order", "%(playlist_index)03d - %(title)s.%(ext)s"),
+                TemplatePreset::new("Resolution", "%(title)s [%(resolution)s].%(ext)s"),
+            ],
+        }
+    }
+}
+
+impl TemplateSettings {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for (i, preset) in self.presets.iter().enumerate() {
+            if preset.name.trim().is_empty() {
+                return Err("Template presets must have a name".into());
+            }
+
+            if self.presets[..i]
+                .iter()
+                .any(|other| other.name == preset.name)
+            {
+                return Err(format!("Two template presets are named {}", preset.name));
+            }
+
+            validate_template(&preset.template)
+                .map_err(|e| format!("Invalid template preset {}: {}", preset.name, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that an output template gives a relative path yt-dlp and Windows accept.
+///
+/// Fields are only checked for their syntax, any field yt-dlp knows (`uploader`, `upload_date`,
+/// `id`, `playlist_index`, `resolution`...) can be used. Fields a video doesn't have are
+/// written as `NA`.
+pub(crate) fn validate_template(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err("The template is empty".into());
+    }
+
+    // Sections and chapters add their own suffix before the extension
+    if !template.ends_with(".%(ext)s") {
+        return Err("The template must end with .%(ext)s".into());
+    }
+
+    if template.starts_with(['/', '\\']) {
+        return Err("The template must be relative to the download folder".into());
+    }
+
+    let literal = literal_text(template)?;
+
+    if let Some(c) = literal.chars().find(|c| FORBIDDEN_CHARACTERS.contains(c)) {
+        return Err(format!(
+            "The template can't contain {} outside of fields",
+            c
+        ));
+    }
+
+    for segment in template.split(['/', '\\']) {
+        if segment.trim().is_empty() {
+            return Err("The template has an empty folder name".into());
+        }
+
+        if segment == "." || segment == ".." {
+            return Err("The template can't contain . or .. folders".into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the syntax of the fields of a template that isn't a path, like a metadata template.
+pub(crate) fn check_fields(template: &str) -> Result<(), String> {
+    literal_text(template).map(|_| ())
+}
+
+/// The template with its `%(...)X` fields removed, checking that each of them is complete.
+fn literal_text(template: &str) -> Result<String, String> {
+    let mut literal = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('%') {
+        literal.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        // "%%" is a literal percent sign
+        if let Some(after) = rest.strip_prefix('%') {
+            literal.push('%');
+            rest = after;
+            continue;
+        }
+
+        let Some(field) = rest.strip_prefix('(') else {
+            return Err("A % must start a field like %(title)s, or be written %%".into());
+        };
+
+        let end = field_end(field).ok_or("A field is missing its closing parenthesis")?;
+        let name = &field[..end];
+        if name.trim().is_empty() {
+            return Err("A field has no name".into());
+        }
+
+        // Flags, width and precision can come before the conversion, e.g. "03d"
+        let after = &field[end + 1..];
+        let format = after.trim_start_matches(|c: char| "#0-+ .".contains(c) || c.is_ascii_digit());
+        match format.chars().next() {
+            Some(c) if CONVERSIONS.contains(c) => rest = &format[c.len_utf8()..],
+            _ => {
+                return Err(format!(
+                    "The field %({}) has no conversion, like s or d",
+                    name
+                ))
+            }
+        }
+    }
+
+    literal.push_str(rest);
+
+    Ok(literal)
+}
+
+/// Index of the parenthesis that closes a field, skipping nested parentheses.
+fn field_end(field: &str) -> Option<usize> {
+    let mut depth = 0;
+
+    for (i, c) in field.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(i),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Extensions yt-dlp can embed a thumbnail into as cover art.
+const EMBED_EXTENSIONS: [&str; 8] = ["mp3", "m4a", "mp4", "m4v", "mov", "mkv", "opus", "flac"];
+
+/// Image format the written thumbnail is converted to. yt-dlp often gets `.webp` thumbnails,
+/// which many players and editors can't open.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ThumbnailFormat {
+    Jpg,
+    Png,
+}
+
+impl ThumbnailFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpg => "jpg",
+            ThumbnailFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ThumbnailOptions {
+    /// Embed the thumbnail as cover art (mp3, m4a, mp4, mkv, opus and flac)
+    #[serde(default)]
+    pub(crate) embed: bool,
+    /// Convert the thumbnail written by `download_thumbnail` (and the embedded one) to this format
+    #[serde(default)]
+    pub(crate) convert: Option<ThumbnailFormat>,
+    /// Use the thumbnail with the most pixels instead of the one the site prefers
+    #[serde(default)]
+    pub(crate) largest: bool,
+}
+
+impl ThumbnailOptions {
+    /// Checks that the thumbnail can be embedded into a file with this extension. `None` is an
+    /// extension only known once yt-dlp is done, which is left to yt-dlp.
+    pub(crate) fn check_embed(&self, ext: Option<&str>) -> Result<(), String> {
+        match ext {
+            Some(ext) if self.embed && !EMBED_EXTENSIONS.contains(&ext) => Err(format!(
+                "Thumbnails can't be embedded into {} files, only into {}",
+                ext,
+                EMBED_EXTENSIONS.join(", ")
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Builds the yt-dlp arguments that write, convert and embed the thumbnail.
+pub(crate) fn thumbnail_args(write: bool, thumbnail: &ThumbnailOptions) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+
+    // When both are set, the written thumbnail is kept after it's embedded
+    if write {
+        args.push("--write-thumbnail".into());
+    }
+
+    if thumbnail.embed {
+        args.push("--embed-thumbnail".into());
+    }
+
+    if let Some(format) = thumbnail.convert {
+        args.push("--convert-thumbnails".into());
+        args.push(format.name().into());
+    }
+
+    args
+}
+
+/// Ranks the thumbnails of a video's info JSON by their size, so yt-dlp picks the largest one.
+///
+/// yt-dlp ranks thumbnails by the `preference` the site gives them before their size, and uses
+/// the best ranked one. Thumbnails without a size are ranked last.
+pub(crate) fn prefer_largest_thumbnail(info: &mut Value) {
+    let Some(thumbnails) = info.get_mut("thumbnails").and_then(Value::as_array_mut) else {
+        return;
+    };
+
+    for thumbnail in thumbnails {
+        let size =
+            ["width", "height"].map(|dimension| thumbnail.get(dimension).and_then(Value::as_u64));
+        let preference = match size {
+            [Some(width), Some(height)] => (width * height) as i64,
+            _ => -1,
+        };
+
+        thumbnail["preference"] = preference.into();
+    }
+}
@@ -0,0 +1,315 @@
+use regex::Regex;
+use serde::Serialize;
+
+/// YouTube hosts, once `www.`, `m.` or `music.` is removed.
+const YOUTUBE_HOSTS: [&str; 3] = ["youtube.com", "youtube-nocookie.com", "youtu.be"];
+
+/// A URL cleaned up before it's handed to yt-dlp.
+///
+/// YouTube links are reduced to the video, playlist or clip they point to, in their canonical
+/// form and without tracking parameters (`si=`, `t=`, `feature=`...).
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum NormalizedUrl {
+    Video {
+        id: String,
+        url: String,
+    },
+    Playlist {
+        id: String,
+        url: String,
+    },
+    Clip {
+        id: String,
+        url: String,
+    },
+    /// Another site, or a YouTube page that is not a single video or playlist (channels...),
+    /// left to yt-dlp as it is
+    Other {
+        url: String,
+    },
+}
+
+impl NormalizedUrl {
+    pub(crate) fn url(&self) -> &str {
+        match self {
+            NormalizedUrl::Video { url, .. }
+            | NormalizedUrl::Playlist { url, .. }
+            | NormalizedUrl::Clip { url, .. }
+            | NormalizedUrl::Other { url } => url,
+        }
+    }
+
+    fn video(id: &str) -> Result<NormalizedUrl, String> {
+        check_id(id, r"^[A-Za-z0-9_-]{11}$", "video")?;
+
+        Ok(NormalizedUrl::Video {
+            id: id.to_string(),
+            url: format!("https://www.youtube.com/watch?v={}", id),
+        })
+    }
+
+    fn playlist(id: &str) -> Result<NormalizedUrl, String> {
+        check_id(id, r"^[A-Za-z0-9_-]{2,}$", "playlist")?;
+
+        Ok(NormalizedUrl::Playlist {
+            id: id.to_string(),
+            url: format!("https://www.youtube.com/playlist?list={}", id),
+        })
+    }
+
+    fn clip(id: &str) -> Result<NormalizedUrl, String> {
+        check_id(id, r"^[A-Za-z0-9_-]+$", "clip")?;
+
+        Ok(NormalizedUrl::Clip {
+            id: id.to_string(),
+            url: format!("https://www.youtube.com/clip/{}", id),
+        })
+    }
+}
+
+/// Checks and normalizes a URL typed or pasted by the user.
+///
+/// Handles `youtube.com/watch`, `youtu.be`, Shorts, `/live/`, embeds (including
+/// `youtube-nocookie.com`), playlists and clips, on the `www.`, `m.` and `music.` subdomains.
+pub(crate) fn normalize_url(input: &str) -> Result<NormalizedUrl, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("The URL is empty".into());
+    }
+
+    // Links copied without the scheme ("youtu.be/...") are common
+    let url = if input.contains("://") {
+        input.to_string()
+    } else {
+        format!("https://{}", input)
+    };
+
+    let parts =
+        Regex::new(r"^(?i:https?)://([^/?#\s]+)([^?#\s]*)(?:\?([^#\s]*))?(?:#\S*)?$").unwrap();
+    let Some(captures) = parts.captures(&url) else {
+        return Err(format!("Not a valid URL: {}", input));
+    };
+
+    let host = captures[1].to_lowercase();
+    // Ports and credentials don't matter here
+    let host = host.rsplit('@').next().unwrap_or_default();
+    let host = host.split(':').next().unwrap_or_default();
+    let host = ["www.", "m.", "music."]
+        .iter()
+        .find_map(|prefix| host.strip_prefix(prefix))
+        .unwrap_or(host);
+
+    if !YOUTUBE_HOSTS.contains(&host) {
+        return Ok(NormalizedUrl::Other { url });
+    }
+
+    let query: Vec<(&str, &str)> = captures
+        .get(3)
+        .map_or("", |query| query.as_str())
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .collect();
+    let param = |name: &str| {
+        query
+            .iter()
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| *value)
+    };
+
+    let path: Vec<&str> = captures
+        .get(2)
+        .map_or("", |path| path.as_str())
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match (host, path.as_slice()) {
+        ("youtu.be", [id]) => NormalizedUrl::video(id),
+        ("youtu.be", _) => Err(format!("The URL has no video ID: {}", input)),
+        // A video opened from a playlist is downloaded alone
+        (_, ["watch"]) => match (param("v"), param("list")) {
+            (Some(id), _) => NormalizedUrl::video(id),
+            (None, Some(list)) => NormalizedUrl::playlist(list),
+            (None, None) => Err(format!("The URL has no video ID: {}", input)),
+        },
+        (_, ["playlist"] | ["embed", "videoseries"]) => match param("list") {
+            Some(list) => NormalizedUrl::playlist(list),
+            None => Err(format!("The URL has no playlist ID: {}", input)),
+        },
+        (_, ["shorts" | "live" | "embed" | "v" | "e", id]) => NormalizedUrl::video(id),
+        (_, ["clip", id]) => NormalizedUrl::clip(id),
+        _ => Ok(NormalizedUrl::Other { url }),
+    }
+}
+
+fn check_id(id: &str, pattern: &str, kind: &str) -> Result<(), String> {
+    if Regex::new(pattern).unwrap().is_match(id) {
+        Ok(())
+    } else {
+        Err(format!("Invalid YouTube {} ID: {}", kind, id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIDEO: &str = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+
+    fn url(input: &str) -> String {
+        normalize_url(input).unwrap().url().to_string()
+    }
+
+    #[test]
+    fn normalizes_video_links() {
+        for input in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "http://youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+            "HTTPS://WWW.YOUTUBE.COM/watch?v=dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ",
+            "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ",
+            "https://www.youtube.com/v/dQw4w9WgXcQ",
+            "https://www.youtube.com:443/watch?v=dQw4w9WgXcQ",
+        ] {
+            assert_eq!(url(input), VIDEO, "{}", input);
+        }
+
+        assert_eq!(
+            normalize_url("https://youtu.be/dQw4w9WgXcQ").unwrap(),
+            NormalizedUrl::Video {
+                id: "dQw4w9WgXcQ".into(),
+                url: VIDEO.into(),
+            }
+        );
+    }
+
+    #[test]
+    fn adds_missing_scheme() {
+        assert_eq!(url("youtu.be/dQw4w9WgXcQ"), VIDEO);
+        assert_eq!(url("www.youtube.com/watch?v=dQw4w9WgXcQ"), VIDEO);
+        assert_eq!(url("  https://youtu.be/dQw4w9WgXcQ \n"), VIDEO);
+    }
+
+    #[test]
+    fn strips_tracking_parameters() {
+        for input in [
+            "https://youtu.be/dQw4w9WgXcQ?si=AbCdEfGhIjKlMnOp",
+            "https://youtu.be/dQw4w9WgXcQ?t=42",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1m30s&si=xyz",
+            "https://www.youtube.com/watch?feature=share&v=dQw4w9WgXcQ",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ?feature=share",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ#t=30",
+        ] {
+            assert_eq!(url(input), VIDEO, "{}", input);
+        }
+    }
+
+    #[test]
+    fn downloads_videos_opened_from_playlists_alone() {
+        assert_eq!(
+            url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf&index=3"),
+            VIDEO
+        );
+    }
+
+    #[test]
+    fn normalizes_playlist_links() {
+        let playlist = "https://www.youtube.com/playlist?list=PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf";
+
+        for input in [
+            "https://www.youtube.com/playlist?list=PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf",
+            "https://music.youtube.com/playlist?list=PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf&si=abc",
+            "https://www.youtube.com/watch?list=PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf",
+            "https://www.youtube.com/embed/videoseries?list=PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf",
+        ] {
+            assert_eq!(url(input), playlist, "{}", input);
+        }
+
+        assert!(matches!(
+            normalize_url(playlist).unwrap(),
+            NormalizedUrl::Playlist { id, .. } if id == "PLrAXtmErZgOeiKm4sgNOknGvNjby9efdf"
+        ));
+    }
+
+    #[test]
+    fn normalizes_clip_links() {
+        assert_eq!(
+            normalize_url("https://youtube.com/clip/UgkxAbC-12_xyz?si=abc").unwrap(),
+            NormalizedUrl::Clip {
+                id: "UgkxAbC-12_xyz".into(),
+                url: "https://www.youtube.com/clip/UgkxAbC-12_xyz".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_ids() {
+        for input in [
+            "https://youtu.be/dQw4w9WgXc",
+            "https://youtu.be/dQw4w9WgXcQQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgX!Q",
+            "https://www.youtube.com/shorts/short",
+            "https://www.youtube.com/playlist?list=P",
+            "https://www.youtube.com/clip/bad!clip",
+        ] {
+            assert!(normalize_url(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_links_without_an_id() {
+        for input in [
+            "https://youtu.be/",
+            "https://www.youtube.com/watch",
+            "https://www.youtube.com/watch?v=",
+            "https://www.youtube.com/playlist",
+            "https://www.youtube.com/playlist?list=",
+        ] {
+            assert!(normalize_url(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_urls() {
+        for input in [
+            "",
+            "   ",
+            "not a url",
+            "https://",
+            "https://you tube.com/watch",
+        ] {
+            assert!(normalize_url(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn leaves_other_pages_and_sites_as_they_are() {
+        for input in [
+            "https://www.youtube.com/@RickAstleyYT",
+            "https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw",
+            "https://www.twitch.tv/videos/123456789",
+            "https://x.com/user/status/1234567890?s=20",
+            "https://notyoutube.com/watch?v=dQw4w9WgXcQ",
+        ] {
+            assert_eq!(
+                normalize_url(input).unwrap(),
+                NormalizedUrl::Other { url: input.into() },
+                "{}",
+                input
+            );
+        }
+
+        assert_eq!(
+            normalize_url("twitch.tv/videos/123456789").unwrap(),
+            NormalizedUrl::Other {
+                url: "https://twitch.tv/videos/123456789".into()
+            }
+        );
+    }
+}
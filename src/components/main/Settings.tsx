@@ -51,10 +51,18 @@ export default function Settings() {
     
     async function handleDownloadDeps() {
         setReinstallDeps(true)
-        const deps = await TauriApi.Ytdlp.TauriYtdlpApi.DownloadDependencies() as {
-            ffmpeg: { success: boolean, message: string },
-            ytdlp: { success: boolean, message: string }
-        };
+        let deps;
+        try {
+            deps = await TauriApi.Ytdlp.TauriYtdlpApi.DownloadDependencies() as {
+                ffmpeg: { success: boolean, message: string },
+                ytdlp: { success: boolean, message: string }
+            };
+        } catch (err) {
+            // Invalid network settings, like a malformed proxy
+            console.error("Error downloading dependencies:", err);
+            setReinstallDeps(false);
+            return setReinstallDepsError(`Erro ao baixar as dependências: ${err}`)
+        }
         
         if (deps.ffmpeg.success && deps.ytdlp.success) {
             if (verifyDepsError.type === "error") {
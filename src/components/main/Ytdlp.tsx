@@ -4,7 +4,7 @@ import {Input} from "@/components/ui/input";
 import {TauriApi} from "@/lib/tauri";
 import Image from "next/image";
 import {Button} from "@/components/ui/button";
-import GetVideoType, {VideoType} from "@/helpers/GetVideoType";
+import GetVideoType from "@/helpers/GetVideoType";
 import {Dialog, DialogContent, DialogHeader, DialogTitle, DialogTrigger} from "@/components/ui/dialog";
 import {Checkbox} from "@/components/ui/checkbox";
 import * as Progress from '@radix-ui/react-progress';
@@ -76,6 +76,12 @@ export default function Ytdlp() {
         speed: "0MiB/s",
         eta: "Sem previsão"
     });
+    // Entry of a playlist being downloaded, null for single videos
+    const [playlistEntry, setPlaylistEntry] = useState<{
+        index: number;
+        total: number;
+        title: string;
+    } | null>(null);
     const [downloadStarted, setDownloadStarted] = useState<boolean>(false);
     const [downloadComplete, setDownloadComplete] = useState<boolean>(false);
     
@@ -105,7 +111,7 @@ export default function Ytdlp() {
         const fetchVideoInfo = async () => {
             try {
                 const info = await TauriApi.Ytdlp.TauriYtdlpApi.GetVideoInfo(url);
-                const [title, ext, thumbnail, uploader] = info as [string, string, string, string];
+                const {title, ext, thumbnail, uploader} = info as VideoInfo;
                 setVideoInfo({title, ext, thumbnail, uploader});
                 setStartSearch(false)
             } catch (err) {
@@ -124,7 +130,7 @@ export default function Ytdlp() {
         
         const videoType = GetVideoType(url);
         
-        if (!videoType || videoType === VideoType.Playlist) {
+        if (!videoType) {
             setError("URL inválida");
             return;
         }
@@ -145,10 +151,16 @@ export default function Ytdlp() {
         
         try {
             setDownloadStarted(true);
+            setPlaylistEntry(null);
             
-            await TauriApi.Ytdlp.TauriYtdlpApi.DownloadVideo(opts);
+            const jobId = await TauriApi.Ytdlp.TauriYtdlpApi.DownloadVideo(opts);
+            
+            // Events of every download in the queue arrive here, only this one's are shown
+            const isThisJob = (data: any) => data.payload.jobId === jobId;
             
             const completeEventListener = new TauriApi.Ytdlp.YtdlpEventListener("download_complete", (data: any) => {
+                if (!isThisJob(data)) return;
+                
                 setDownloadStarted(false);
                 setDownloadData({
                     downloaded: "",
@@ -161,40 +173,59 @@ export default function Ytdlp() {
             });
             
             const errorEventListener = new TauriApi.Ytdlp.YtdlpEventListener("download_error", (data: any) => {
+                if (!isThisJob(data)) return;
+                
                 console.error("Download error", data);
                 setError("Não foi possível baixar o vídeo. Por favor, tente novamente.");
                 setDownloadStarted(false);
             });
             
+            const updateProgress = (progress: any) => {
+                const {percent, totalBytes, speed, eta} = progress as {
+                    phase: string;
+                    percent: number | null;
+                    downloadedBytes: number | null;
+                    totalBytes: number | null;
+                    speed: number | null;
+                    eta: number | null;
+                };
+                
+                if (percent === null) return;
+                
+                const toMiB = (bytes: number) => `${(bytes / 1024 / 1024).toFixed(2)}MiB`;
+                
+                setDownloadData({
+                    downloaded: `${percent.toFixed(1)}%`,
+                    percentage: percent,
+                    total: totalBytes !== null ? toMiB(totalBytes) : "",
+                    speed: speed !== null ? `${toMiB(speed)}/s` : "",
+                    eta: eta !== null ? `${String(Math.floor(eta / 60)).padStart(2, "0")}:${String(eta % 60).padStart(2, "0")}` : "Sem previsão"
+                });
+            };
+            
             const downloadProgressListener = new TauriApi.Ytdlp.YtdlpEventListener("download_progress", (data: any) => {
+                if (!isThisJob(data)) return;
                 
-                const regex = /\[download]\s+\d+\.\d+% of\s+[\d.]+MiB at\s+(?:Unknown B\/s|[\d.]+MiB\/s) ETA (?:Unknown|\d{2}:\d{2})/;
-                const match = data.payload.match(regex);
-                if (match) {
-                    const percentage = match[1];
-                    const fileSize = match[2];
-                    const speed = match[3];
-                    const eta = match[4];
-                    
-                    const percentageNumber = parseFloat(percentage.replace("%", ""));
-                    
-                    setDownloadData({
-                        downloaded: percentage,
-                        percentage: percentageNumber,
-                        total: fileSize,
-                        speed,
-                        eta
-                    });
-                    
-                    if (percentage.includes("100")) {
-                        setDownloadStarted(false);
-                    }
-                } else {
-                    console.log("No match found");
-                }
+                updateProgress(data.payload);
+            });
+            
+            // Playlists report the progress of the entry being downloaded
+            const playlistProgressListener = new TauriApi.Ytdlp.YtdlpEventListener("playlist_progress", (data: any) => {
+                if (!isThisJob(data)) return;
+                
+                const {index, total, title, progress} = data.payload as {
+                    index: number;
+                    total: number;
+                    title: string;
+                    progress: unknown;
+                };
+                
+                setPlaylistEntry({index, total, title});
+                updateProgress(progress);
             });
             
             await downloadProgressListener.listen();
+            await playlistProgressListener.listen();
             await completeEventListener.listen();
             await errorEventListener.listen();
             return;
@@ -339,6 +370,13 @@ export default function Ytdlp() {
                                     {
                                         downloadStarted && (
                                             <div className={"flex flex-col justify-center items-center m-2"}>
+                                                {
+                                                    playlistEntry && (
+                                                        <span className={"text-muted-foreground"}>
+                                                            Vídeo {playlistEntry.index} de {playlistEntry.total}: {playlistEntry.title}
+                                                        </span>
+                                                    )
+                                                }
                                                 <div className={"flex flex-row justify-start items-start m-1"}>
                                                     <span className={"text-muted-foreground"}>
                                                         Baixado: {downloadData.downloaded} de {downloadData.total} - {downloadData.speed} - ETA: {downloadData.eta}
@@ -10,7 +10,9 @@ export default function GetVideoType(videoUrl: string): VideoType | null {
         [/https?:\/\/(www\.)?youtube\.com\/clip\//, VideoType.Clip],
         [/https?:\/\/(www\.)?youtube\.com\/playlist\?list=/, VideoType.Playlist],
         [/https?:\/\/(www\.)?youtube\.com\/watch\?v=[^&]+&live/, VideoType.Livestream],
-        [/https?:\/\/(www\.)?youtube\.com\/watch\?v=/, VideoType.Video]
+        [/https?:\/\/(www\.)?youtube\.com\/watch\?v=/, VideoType.Video],
+        // Other sites (Twitch, X, Niconico...) are classified by yt-dlp when fetching the video
+        [/^https?:\/\/\S+$/, VideoType.Video]
     ];
     
     for (const [pattern, type] of patterns) {
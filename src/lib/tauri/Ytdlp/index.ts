@@ -11,14 +11,14 @@ class TauriYtdlpApi {
         return this.command("get_dependencies", {});
     }
     
-    static async GetVideoInfo(url: string) {
+    static async GetVideoInfo(url: string, cookies?: unknown) {
         
         console.log("Getting video info")
-        return this.command("fetch_video", {url});
+        return this.command("fetch_video", {url, cookies});
     }
     
     
-    // fn download_video(url: String, format: Option<String>, path: String, unique_folders: bool, download_thumbnail: bool, write_url_link: bool) -> Result<bool, Box<dyn Error>> {
+    // fn download_video_command(url: String, options: DownloadOptions) -> Result<u64, String>, resolves to the job ID
     static async DownloadVideo(
         {
             url,
@@ -27,6 +27,7 @@ class TauriYtdlpApi {
             downloadThumbnail,
             writeUrlLink,
             format,
+            ...extraOptions
         }: {
             url: string,
             path: string,
@@ -34,11 +35,136 @@ class TauriYtdlpApi {
             downloadThumbnail: boolean,
             writeUrlLink: boolean,
             format?: string,
+            // Any other field of the backend's DownloadOptions, e.g. formatSelection
+            [option: string]: unknown,
         }
     ) {
         
         console.log("Downloading video")
-        return this.command("download_video_command", {url, format, path, uniqueFolders, downloadThumbnail, writeUrlLink});
+        return this.command<number>("download_video_command", {
+            url,
+            options: {format, path, uniqueFolders, downloadThumbnail, writeUrlLink, ...extraOptions}
+        });
+    }
+    
+    // Same options as DownloadVideo, resolves to the path the file would be saved as
+    static async PreviewOutputPath(url: string, options: Record<string, unknown>) {
+        return this.command<string>("preview_output_path", {url, options});
+    }
+    
+    static async CheckOutputTemplate(template: string) {
+        return this.command<void>("check_output_template", {template});
+    }
+    
+    // Error of a failed download, as sent on download_error and kept in the job
+    static isDownloadError(error: unknown): error is { code: string, message: string, details: string } {
+        return typeof error === "object" && error !== null && "code" in error && "message" in error;
+    }
+    
+    static async ListLogs() {
+        return this.command<{ name: string, size: number, modified: string }[]>("list_logs", {});
+    }
+    
+    static async ReadLog(name: string) {
+        return this.command<string>("read_log", {name});
+    }
+    
+    // Every log when names is empty, resolves to how many logs were zipped
+    static async ExportLogs(path: string, names: string[] = []) {
+        return this.command<number>("export_logs", {path, names});
+    }
+    
+    static async SanitizeFilename(name: string) {
+        return this.command<string>("sanitize_filename", {name});
+    }
+    
+    static async FetchFormats(url: string, cookies?: unknown) {
+        return this.command("fetch_formats", {url, cookies});
+    }
+    
+    // Resolves to {type, id, url} with the canonical URL, rejects invalid URLs with an error message
+    static async CheckUrl(url: string) {
+        return this.command<{ type: string, id?: string, url: string }>("check_url", {url});
+    }
+    
+    static async ImportCookies(path: string) {
+        return this.command("import_cookies", {path});
+    }
+    
+    static async HasImportedCookies() {
+        return this.command<boolean>("has_imported_cookies", {});
+    }
+    
+    static async DeleteCookies() {
+        return this.command("delete_cookies", {});
+    }
+    
+    static async ListDownloads() {
+        return this.command("list_downloads", {});
+    }
+    
+    static async MoveDownload(jobId: number, position: number) {
+        return this.command("move_download", {jobId, position});
+    }
+    
+    static async RemoveDownload(jobId: number) {
+        return this.command("remove_download", {jobId});
+    }
+    
+    static async CancelDownload(jobId: number, deleteFiles: boolean) {
+        return this.command("cancel_download", {jobId, deleteFiles});
+    }
+    
+    static async PauseDownload(jobId: number) {
+        return this.command("pause_download", {jobId});
+    }
+    
+    static async StopRecording(jobId: number) {
+        return this.command("stop_recording", {jobId});
+    }
+    
+    static async ResumeDownload(jobId: number) {
+        return this.command("resume_download", {jobId});
+    }
+    
+    static async SetMaxConcurrentDownloads(limit: number) {
+        return this.command("set_max_concurrent_downloads", {limit});
+    }
+    
+    static async ListHistory(filter?: { search?: string, outcome?: string, since?: string, until?: string }) {
+        return this.command("list_history", {filter});
+    }
+    
+    static async ClearHistory() {
+        return this.command("clear_history", {});
+    }
+    
+    static async Redownload(recordId: number) {
+        return this.command<number>("redownload", {recordId});
+    }
+    
+    static async ListArchive() {
+        return this.command("list_archive", {});
+    }
+    
+    static async RemoveFromArchive(entries: { extractor: string, id: string }[]) {
+        return this.command("remove_from_archive", {entries});
+    }
+    
+    static async ImportArchive(path: string) {
+        return this.command<number>("import_archive", {path});
+    }
+    
+    static async ExportArchive(path: string) {
+        return this.command("export_archive", {path});
+    }
+    
+    static async GetSettings() {
+        return this.command("get_settings", {});
+    }
+    
+    static async UpdateSettings(settings: unknown) {
+        return this.command("update_settings", {settings});
     }
     
     static async GetDefaultPath() {
@@ -70,7 +196,7 @@ class TauriYtdlpApi {
 class YtdlpEventListener {
     private unlisten: (() => void) | null = null;
     
-    constructor(public event: "ytdlp_deps_progress" | "download_progress" | "download_complete" | "download_error", public callback: (event: Event<unknown>) => void) {
+    constructor(public event: "ytdlp_deps_progress" | "download_progress" | "download_complete" | "download_error" | "download_retrying" | "download_paused" | "download_cancelled" | "download_queue_update" | "playlist_progress" | "playlist_entry_error" | "playlist_entry_retrying" | "history_error", public callback: (event: Event<unknown>) => void) {
     }
     
     async listen() {
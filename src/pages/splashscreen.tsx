@@ -31,7 +31,8 @@ export default function Splashscreen() {
         if (typeof window !== undefined) {
             // Define an async function inside useEffect
             const checkDependencies = async () => {
-                await HandleDependencies();
+                // The main window opens anyway, so the network settings can be fixed there
+                await HandleDependencies().catch((err) => console.error("Error downloading dependencies:", err));
                 TauriApi.InvokeMainWindow();
             };
 